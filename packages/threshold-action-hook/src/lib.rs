@@ -0,0 +1,30 @@
+//! Interface for receiver contracts notified when a campaign succeeds.
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{to_binary, Coin, CosmosMsg, StdResult, WasmMsg};
+
+#[cw_serde]
+pub struct ThresholdReachedHook {
+    /// Address of the campaign.
+    pub campaign: String,
+    /// Value raised, in the threshold's denom.
+    pub total: Coin,
+    pub contributors: Vec<String>,
+}
+
+/// Message a receiver contract must accept, alongside its own execute messages.
+#[cw_serde]
+pub enum ReceiverExecuteMsg {
+    ThresholdReachedHook(ThresholdReachedHook),
+}
+
+impl ThresholdReachedHook {
+    /// Calls `contract_addr` with this hook, attaching `funds`.
+    pub fn into_cosmos_msg(self, contract_addr: String, funds: Vec<Coin>) -> StdResult<CosmosMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr,
+            msg: to_binary(&ReceiverExecuteMsg::ThresholdReachedHook(self))?,
+            funds,
+        }
+        .into())
+    }
+}
@@ -1,39 +1,246 @@
 use cosmwasm_schema::cw_serde;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
-    entry_point, to_binary, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo,
-    Order, Response, StdResult, Timestamp, Uint128,
+    entry_point, from_binary, to_binary, Addr, BankMsg, Binary, Coin, ContractInfoResponse,
+    CosmosMsg, Decimal, Deps, DepsMut, Env, Event, MessageInfo, Order, Reply, Response, StdError,
+    StdResult, Storage, SubMsg, SubMsgResult, Uint128, WasmMsg, WasmQuery,
 };
 use cw2::set_contract_version;
+use cw20::{AllowanceResponse, BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
+use cw_utils::{Duration, Expiration, NativeBalance};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 
+use crate::credential::{self, Credential, SignedCredential};
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{CONTRIBUTIONS, DEADLINE, RECEIVER, THRESHOLD_COIN};
+use crate::factory::{FactoryConfigResponse, FactoryQueryMsg};
+use crate::hook::ThresholdReachedHook;
+use crate::intent::{self, SignedIntent};
+use crate::merkle::{self, Hash};
+use crate::msg::{
+    ActionsResponse, AllowlistProof, AllowlistResponse, BeneficiaryContribution,
+    ContributionCoinsResponse, CredentialResponse, DeadlineResponse, ExecuteMsg, ExtensionResponse,
+    FailedAction, FailedPayoutResponse, InstantiateMsg, IntentNonceResponse, OwnershipResponse,
+    PauseResponse, PledgeResponse, QueryMsg, ReceiveMsg, ReceiversResponse, StatusResponse,
+    SudoMsg,
+};
+use crate::oracle::query_price;
+use crate::state::{
+    same_unit, AllowlistEntry, CancelWindow, ExtensionProposal, FeeSource, GuardianConfig, Outcome,
+    Pause, Payout, Pull, ReceiverProposal, ReceiverShare, RefundPolicy, ThresholdSchedule, ACTIONS,
+    ACTION_REPLIES, ALLOWANCE_PLEDGES, ALLOWLISTED, ALLOWLIST_ROOT, ANTI_SNIPE, ATTESTER,
+    AUTO_EXTENDED, CANCEL_WINDOW, CONTRIBUTIONS, CREDENTIALS, CW20_TOKEN, DEADLINE,
+    DEFAULTED_PLEDGES, DENOM_WEIGHTS, EXTENSION_CONFIG, EXTENSION_COUNT, EXTENSION_VOTES,
+    FAILED_ACTIONS, FAILED_PAYOUTS, FEE, FRONTED, GUARDIAN, INTENT_NONCES, LATE_PLEDGERS,
+    MAX_FEE_BPS, MAX_PAUSES, MAX_PAUSE_BLOCKS, MAX_PAUSE_SECONDS, MIN_CONTRIBUTION, OPENING,
+    ORACLE, OUTCOME, OWNER, PAUSES, PAUSE_COUNT, PAYOUTS, PENDING_EXTENSION, PENDING_OWNER, PULLS,
+    RECEIVERS, RECEIVER_HOOK, RECEIVER_PROPOSAL, REFUND_POLICY, REFUND_TAXES, REPLY_COUNT,
+    REVOKED_NONCES, SCHEDULE_END, START, THRESHOLD_COIN, THRESHOLD_SCHEDULE, TOTAL_BPS,
+    USED_NONCES,
+};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:threshold-funding";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const MAX_ACTIONS: usize = 16;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+    if let Expiration::Never {} = msg.deadline {
+        return Err(ContractError::InvalidDeadline {
+            reason: "campaign must end".to_string(),
+        });
+    }
+
+    let schedule = msg
+        .threshold_schedule
+        .unwrap_or(ThresholdSchedule::Constant {});
+    schedule
+        .validate(msg.coin_threshold.amount, &msg.deadline)
+        .map_err(|reason| ContractError::InvalidThresholdSchedule { reason })?;
+
+    if let Some(extensions) = &msg.extensions {
+        if extensions.veto_threshold > Decimal::one() {
+            return Err(ContractError::InvalidExtension {
+                reason: "veto threshold is above 1".to_string(),
+            });
+        }
+        if !same_unit(&extensions.veto_period, &msg.deadline) {
+            return Err(ContractError::InvalidExtension {
+                reason: "veto period and deadline must use the same unit".to_string(),
+            });
+        }
+        EXTENSION_CONFIG.save(deps.storage, extensions)?;
+    }
+
+    if let Some(anti_snipe) = &msg.anti_snipe {
+        let durations = [
+            &anti_snipe.window,
+            &anti_snipe.extension,
+            &anti_snipe.max_extension,
+        ];
+        if !durations
+            .iter()
+            .all(|duration| same_unit(duration, &msg.deadline))
+        {
+            return Err(ContractError::InvalidExtension {
+                reason: "anti-snipe durations and deadline must use the same unit".to_string(),
+            });
+        }
+        ANTI_SNIPE.save(deps.storage, anti_snipe)?;
+    }
+
+    let refund_policy = msg.refund_policy.unwrap_or(RefundPolicy::Free {});
+    if let RefundPolicy::TaxOnThreshold { tax } = refund_policy {
+        if tax > Decimal::one() {
+            return Err(ContractError::InvalidRefundPolicy {
+                reason: "tax is above 1".to_string(),
+            });
+        }
+    }
+    REFUND_POLICY.save(deps.storage, &refund_policy)?;
+    if let Some(min) = msg.min_contribution {
+        MIN_CONTRIBUTION.save(deps.storage, &min)?;
+    }
+
+    if let Some(oracle) = msg.oracle {
+        if msg.accepted_denoms.is_some() {
+            return Err(ContractError::InvalidOracle {
+                reason: "denoms are priced by the oracle".to_string(),
+            });
+        }
+        if oracle.denoms.is_empty() {
+            return Err(ContractError::InvalidOracle {
+                reason: "no denom accepted".to_string(),
+            });
+        }
+        deps.api.addr_validate(&oracle.contract)?;
+        for denom in &oracle.denoms {
+            let price = query_price(
+                &deps.querier,
+                &oracle,
+                denom,
+                &msg.coin_threshold.denom,
+                env.block.time,
+            )?;
+            DENOM_WEIGHTS.save(deps.storage, denom, &price)?;
+        }
+        ORACLE.save(deps.storage, &oracle)?;
+    } else {
+        DENOM_WEIGHTS.save(deps.storage, &msg.coin_threshold.denom, &Decimal::one())?;
+    }
+    for accepted in msg.accepted_denoms.unwrap_or_default() {
+        if accepted.weight.is_zero() || DENOM_WEIGHTS.has(deps.storage, &accepted.denom) {
+            return Err(ContractError::InvalidDenomWeight {
+                denom: accepted.denom,
+            });
+        }
+        DENOM_WEIGHTS.save(deps.storage, &accepted.denom, &accepted.weight)?;
+    }
+
+    if let Some(token) = msg.cw20 {
+        let token = deps.api.addr_validate(&token)?;
+        if !DENOM_WEIGHTS.has(deps.storage, token.as_str()) {
+            return Err(ContractError::UnacceptedDenom {
+                denom: token.into_string(),
+            });
+        }
+        CW20_TOKEN.save(deps.storage, &token)?;
+    }
+
+    if let Some(actions) = msg.actions {
+        validate_actions(deps.as_ref(), &env, &actions)?;
+        ACTIONS.save(deps.storage, &actions)?;
+    }
+
     THRESHOLD_COIN.save(deps.storage, &msg.coin_threshold)?;
+    THRESHOLD_SCHEDULE.save(deps.storage, &schedule)?;
+    START.save(deps.storage, &env.block.time)?;
     DEADLINE.save(deps.storage, &msg.deadline)?;
-    RECEIVER.save(
+    SCHEDULE_END.save(deps.storage, &msg.deadline)?;
+    if let Some(opening) = msg.opening {
+        OPENING.save(deps.storage, &opening)?;
+    }
+    let receivers = match (msg.receiver, msg.receivers) {
+        (Some(_), Some(_)) => {
+            return Err(ContractError::InvalidReceivers {
+                reason: "either a receiver or a list of receivers".to_string(),
+            })
+        }
+        (None, Some(receivers)) => {
+            validate_receivers(deps.as_ref(), &receivers)?;
+            receivers
+        }
+        (receiver, None) => vec![ReceiverShare {
+            address: receiver.unwrap_or_else(|| info.sender.to_string()),
+            share_bps: TOTAL_BPS,
+        }],
+    };
+    if msg.require_receiver_acceptance.unwrap_or_default() {
+        RECEIVER_PROPOSAL.save(
+            deps.storage,
+            &ReceiverProposal {
+                receivers,
+                accepted: vec![],
+            },
+        )?;
+    } else {
+        RECEIVERS.save(deps.storage, &receivers)?;
+    }
+    CANCEL_WINDOW.save(
         deps.storage,
-        &msg.receiver.unwrap_or_else(|| info.sender.to_string()),
+        &msg.cancel_window.unwrap_or(CancelWindow::BeforeDeadline {}),
     )?;
 
+    let fee = match msg.fee {
+        Some(FeeSource::Custom(fee)) => Some(fee),
+        Some(FeeSource::Factory {}) => {
+            let config: FactoryConfigResponse = deps
+                .querier
+                .query_wasm_smart(&info.sender, &FactoryQueryMsg::Config {})?;
+            config.fee
+        }
+        None => None,
+    };
+    if let Some(fee) = fee {
+        deps.api.addr_validate(&fee.treasury)?;
+        if fee.fee_bps > MAX_FEE_BPS {
+            return Err(ContractError::InvalidFee {
+                reason: format!("fee is above {} bps", MAX_FEE_BPS),
+            });
+        }
+        FEE.save(deps.storage, &fee)?;
+    }
+    RECEIVER_HOOK.save(deps.storage, &msg.receiver_hook.unwrap_or_default())?;
+    if let Some(root) = msg.allowlist_root {
+        validate_root(&root)?;
+        ALLOWLIST_ROOT.save(deps.storage, &root)?;
+    }
+    if let Some(attester) = msg.attester {
+        if attester.len() != 33 && attester.len() != 65 {
+            return Err(ContractError::InvalidAttester {});
+        }
+        ATTESTER.save(deps.storage, &attester)?;
+    }
+    if let Some(guardian) = msg.guardian {
+        deps.api.addr_validate(&guardian.address)?;
+        validate_guardian(&guardian)?;
+        GUARDIAN.save(deps.storage, &guardian)?;
+    }
+    OWNER.save(deps.storage, &info.sender)?;
+
     Ok(Response::new()
         .add_attribute("method", "instantiate")
         .add_attribute("owner", info.sender)
-        .add_attribute("deadline-seconds", msg.deadline.seconds().to_string())
+        .add_attribute("deadline", msg.deadline.to_string())
         .add_attribute("threshold-amount", msg.coin_threshold.amount.to_string())
         .add_attribute("threshold-denom", msg.coin_threshold.denom.to_string()))
 }
@@ -46,455 +253,4941 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::ContributionMsg { coin } => execute::contribution(deps, env, info, coin),
+        ExecuteMsg::ContributionMsg {
+            coin,
+            allowlist,
+            credential,
+            beneficiary,
+        } => execute::contribution(deps, env, info, coin, allowlist, credential, beneficiary),
+        ExecuteMsg::ContributionBatch { contributions } => {
+            execute::contribution_batch(deps, env, info, contributions)
+        }
+        ExecuteMsg::Receive(msg) => execute::receive(deps, env, info, msg),
+        ExecuteMsg::UpdateAllowlistRoot { root } => {
+            execute::update_allowlist_root(deps, env, info, root)
+        }
+        ExecuteMsg::PledgeAllowance {
+            amount,
+            allowlist,
+            credential,
+        } => execute::pledge_allowance(deps, env, info, amount, allowlist, credential),
+        ExecuteMsg::RevokeCredential { nonce, signature } => {
+            execute::revoke_credential(deps, env, info, nonce, signature)
+        }
+        ExecuteMsg::SubmitIntent(signed) => execute::submit_intent(deps, env, info, signed),
+        ExecuteMsg::CancelPledge {} => execute::cancel_pledge(deps, env, info),
         ExecuteMsg::RefundMsg {} => execute::refund(deps, env, info, msg),
+        ExecuteMsg::Withdraw { amount, denom } => execute::withdraw(deps, env, info, amount, denom),
+        ExecuteMsg::TransferPledge {
+            to,
+            amount,
+            allowlist,
+            credential,
+        } => execute::transfer_pledge(deps, env, info, to, amount, allowlist, credential),
         ExecuteMsg::ResolveMsg {} => execute::resolve(deps, env, info, msg),
+        ExecuteMsg::RetryPayout {
+            recipient,
+            redirect_to,
+        } => execute::retry_payout(deps, info, recipient, redirect_to),
+        ExecuteMsg::ProposeExtension { deadline } => {
+            execute::propose_extension(deps, env, info, deadline)
+        }
+        ExecuteMsg::ObjectExtension {} => execute::object_extension(deps, env, info),
+        ExecuteMsg::AcceptExtension {} => execute::accept_extension(deps, env, info),
+        ExecuteMsg::Cancel { reason } => execute::cancel(deps, env, info, reason),
+        ExecuteMsg::ClaimRefund {} => execute::claim_refund(deps, env, info),
+        ExecuteMsg::Pause { reason } => execute::pause(deps, env, info, reason),
+        ExecuteMsg::Unpause {} => execute::unpause(deps, env, info),
+        ExecuteMsg::ProposeReceivers { receivers } => {
+            execute::propose_receivers(deps, env, info, receivers)
+        }
+        ExecuteMsg::AcceptReceiverRole {} => execute::accept_receiver_role(deps, env, info),
+        ExecuteMsg::ProposeOwner { new_owner } => {
+            execute::propose_owner(deps, env, info, new_owner)
+        }
+        ExecuteMsg::AcceptOwnership {} => execute::accept_ownership(deps, env, info),
+        ExecuteMsg::RenounceOwnership {} => execute::renounce_ownership(deps, env, info),
     }
 }
 
-pub mod execute {
-    use super::*;
-
-    /// TBD spec
-    pub fn contribution(
-        deps: DepsMut,
-        env: Env,
-        info: MessageInfo,
-        _coin: Coin,
-    ) -> Result<Response, ContractError> {
-        let threshold_coin = THRESHOLD_COIN.load(deps.storage)?;
-        let deadline = DEADLINE.load(deps.storage)?;
+/// Threshold amount in effect at the current block time.
+fn current_threshold(deps: Deps, env: &Env) -> StdResult<Uint128> {
+    let threshold_coin = THRESHOLD_COIN.load(deps.storage)?;
+    let schedule = THRESHOLD_SCHEDULE.load(deps.storage)?;
+    let start = START.load(deps.storage)?;
+    // extensions do not stretch the schedule, a late pledge must not raise the threshold
+    let end = SCHEDULE_END.load(deps.storage)?;
+    Ok(schedule.threshold_at(threshold_coin.amount, start, &end, env.block.time))
+}
 
-        if env.block.time > deadline {
-            // TODO you can be refunded
-            return Err(ContractError::DeadlinePassed {});
+/// Deadline in effect, including an extension whose veto window closed without a veto.
+fn load_deadline(deps: Deps, env: &Env) -> StdResult<Expiration> {
+    let deadline = DEADLINE.load(deps.storage)?;
+    match PENDING_EXTENSION.may_load(deps.storage)? {
+        Some(proposal) if proposal.veto_ends.is_expired(&env.block) => {
+            let config = EXTENSION_CONFIG.load(deps.storage)?;
+            // the deadline may have been pushed past the proposal by anti-sniping
+            if proposal.is_approved(config.veto_threshold) && proposal.deadline > deadline {
+                Ok(proposal.deadline)
+            } else {
+                Ok(deadline)
+            }
         }
+        _ => Ok(deadline),
+    }
+}
 
-        let user = deps.api.addr_validate(info.sender.as_ref())?;
-
-        // TBD check if denom is correct
-        if info.funds.len() != 1 || info.funds[0].denom != threshold_coin.denom {
-            return Err(ContractError::CustomError { val: String::new() });
+/// Persists the deadline in effect, closing an extension proposal whose veto window ended.
+fn settle_deadline(deps: DepsMut, env: &Env) -> StdResult<Expiration> {
+    let deadline = load_deadline(deps.as_ref(), env)?;
+    if let Some(proposal) = PENDING_EXTENSION.may_load(deps.storage)? {
+        if proposal.veto_ends.is_expired(&env.block) {
+            DEADLINE.save(deps.storage, &deadline)?;
+            PENDING_EXTENSION.remove(deps.storage);
         }
+    }
+    Ok(deadline)
+}
 
-        let amount = info.funds[0].amount;
+/// Pushes the deadline out when a contribution or refund lands in the anti-snipe window.
+fn extend_if_late(mut deps: DepsMut, env: &Env) -> StdResult<Option<Expiration>> {
+    let config = match ANTI_SNIPE.may_load(deps.storage)? {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let deadline = settle_deadline(deps.branch(), env)?;
+    if config.window.after(&env.block) < deadline {
+        return Ok(None);
+    }
 
-        // verify that the amount is the same as the ContributionMsg's amount
-        if amount != threshold_coin.amount {
-            return Err(ContractError::CustomError { val: String::new() });
-            // TODO you can trigger a refund
-        }
+    let used = AUTO_EXTENDED.may_load(deps.storage)?.unwrap_or_default();
+    let step = config.next_extension(used);
+    if step == 0 {
+        return Ok(None);
+    }
+    let step_duration = match config.extension {
+        Duration::Height(_) => Duration::Height(step),
+        Duration::Time(_) => Duration::Time(step),
+    };
+    let extended = (deadline + step_duration)?;
+    DEADLINE.save(deps.storage, &extended)?;
+    AUTO_EXTENDED.save(deps.storage, &(used + step))?;
+    Ok(Some(extended))
+}
 
-        CONTRIBUTIONS.update(deps.storage, &user, |old| -> StdResult<Uint128> {
-            match old {
-                Some(old) => Ok(old + amount),
-                None => Ok(amount),
-            }
-        })?;
+/// Extension proposal whose veto window is still open.
+fn open_extension(deps: Deps, env: &Env) -> StdResult<Option<ExtensionProposal>> {
+    Ok(PENDING_EXTENSION
+        .may_load(deps.storage)?
+        .filter(|proposal| !proposal.veto_ends.is_expired(&env.block)))
+}
 
-        Ok(Response::new()
-            .add_attribute("method", "contribution")
-            .add_attribute("contributor", info.sender)
-            .add_attribute("amount", amount.to_string()))
+/// Keeps `user` out of the tally of the open extension proposal, if any, which only counts
+/// what was pledged when it was made.
+fn mark_late(deps: DepsMut, env: &Env, user: &Addr) -> StdResult<()> {
+    if open_extension(deps.as_ref(), env)?.is_some() {
+        let id = EXTENSION_COUNT.load(deps.storage)?;
+        LATE_PLEDGERS.save(deps.storage, (id, user), &true)?;
     }
+    Ok(())
+}
 
-    // refund a single user's contribution
-    // only valid before deadline
-    pub fn refund(
-        deps: DepsMut,
-        env: Env,
-        info: MessageInfo,
-        _: ExecuteMsg,
-    ) -> Result<Response, ContractError> {
-        let deadline = DEADLINE.load(deps.storage)?;
-        let threshold_coin = THRESHOLD_COIN.load(deps.storage)?;
-
-        if env.block.time > deadline {
-            return Err(ContractError::DeadlinePassed {});
-        }
+/// Conversion weight of an accepted denom towards the threshold.
+fn weight(storage: &dyn Storage, denom: &str) -> Result<Decimal, ContractError> {
+    DENOM_WEIGHTS
+        .may_load(storage, denom)?
+        .ok_or_else(|| ContractError::UnacceptedDenom {
+            denom: denom.to_string(),
+        })
+}
 
-        let user = deps.api.addr_validate(info.sender.as_ref())?;
+/// Takes a new oracle price snapshot for `denom`, or for every accepted denom if None.
+/// Nothing to do when the weights are fixed.
+fn refresh_prices(deps: DepsMut, env: &Env, denom: Option<&str>) -> Result<(), ContractError> {
+    let oracle = match ORACLE.may_load(deps.storage)? {
+        Some(oracle) => oracle,
+        None => return Ok(()),
+    };
+    let reference = THRESHOLD_COIN.load(deps.storage)?.denom;
+    let denoms = match denom {
+        Some(denom) => vec![denom.to_string()],
+        None => oracle.denoms.clone(),
+    };
+    for denom in denoms {
+        let price = query_price(&deps.querier, &oracle, &denom, &reference, env.block.time)?;
+        DENOM_WEIGHTS.save(deps.storage, &denom, &price)?;
+    }
+    Ok(())
+}
 
-        let amount = CONTRIBUTIONS
-            .may_load(deps.storage, &user)?
-            .unwrap_or_default();
+/// Messages paying `coins` to `to`: a bank send for native denoms, a cw20 transfer for the
+/// accepted token.
+fn transfer(storage: &dyn Storage, to: &str, coins: Vec<Coin>) -> StdResult<Vec<CosmosMsg>> {
+    Ok(transfers(storage, to, coins, None)?
+        .into_iter()
+        .map(|(_, msg)| msg)
+        .collect())
+}
 
-        if amount.is_zero() {
-            return Err(ContractError::CustomError { val: String::new() });
-        }
+/// Same as `transfer`, each message along with the coins it pays. With a `hook`, the native
+/// coins are attached to a call of the hook instead, sent last whatever the coins.
+fn transfers(
+    storage: &dyn Storage,
+    to: &str,
+    coins: Vec<Coin>,
+    hook: Option<ThresholdReachedHook>,
+) -> StdResult<Vec<(Vec<Coin>, CosmosMsg)>> {
+    let token = CW20_TOKEN.may_load(storage)?;
+    let (tokens, native): (Vec<Coin>, Vec<Coin>) = coins
+        .into_iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .partition(|coin| matches!(&token, Some(token) if token.as_str() == coin.denom));
 
-        CONTRIBUTIONS.remove(deps.storage, &user);
+    let mut msgs = vec![];
+    if !native.is_empty() && hook.is_none() {
+        let msg = CosmosMsg::Bank(BankMsg::Send {
+            to_address: to.to_string(),
+            amount: native.clone(),
+        });
+        msgs.push((native.clone(), msg));
+    }
+    for coin in tokens {
+        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: coin.denom.clone(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: to.to_string(),
+                amount: coin.amount,
+            })?,
+            funds: vec![],
+        });
+        msgs.push((vec![coin], msg));
+    }
+    if let Some(hook) = hook {
+        let msg = hook.into_cosmos_msg(to.to_string(), native.clone())?;
+        msgs.push((native, msg));
+    }
+    Ok(msgs)
+}
 
-        Ok(Response::new()
-            .add_attribute("method", "refund")
-            .add_attribute("contributor", info.sender)
-            .add_attribute("amount", amount.to_string())
-            .add_message(CosmosMsg::Bank(BankMsg::Send {
-                to_address: user.into_string(),
-                amount: vec![Coin {
-                    denom: threshold_coin.denom,
-                    amount,
-                }],
-            })))
+/// Submessages paying `coins` to `to`, a failing one being recorded in `FAILED_PAYOUTS` by
+/// `reply` instead of reverting the whole resolution. They reply whatever the result, for
+/// `reply` to clear their record from `PAYOUTS`.
+fn payouts(
+    storage: &mut dyn Storage,
+    to: &str,
+    coins: Vec<Coin>,
+    hook: Option<ThresholdReachedHook>,
+) -> StdResult<Vec<SubMsg>> {
+    let mut submsgs = vec![];
+    for (coins, msg) in transfers(storage, to, coins, hook)? {
+        let id = next_reply_id(storage)?;
+        PAYOUTS.save(
+            storage,
+            id,
+            &Payout {
+                recipient: to.to_string(),
+                coins,
+            },
+        )?;
+        submsgs.push(SubMsg::reply_always(msg, id));
     }
+    Ok(submsgs)
+}
 
-    /// if threshold isn't reached, refund all contributions
-    /// if threshold is reached, send all funds to the receiver
-    /// can be called anytime
-    pub fn resolve(
-        deps: DepsMut,
-        _: Env,
-        _: MessageInfo,
-        _: ExecuteMsg,
-    ) -> Result<Response, ContractError> {
-        let threshold_coin = THRESHOLD_COIN.load(deps.storage)?;
-        let receiver = RECEIVER.load(deps.storage)?;
+/// Whether `addr` is a contract rather than an account.
+fn is_contract(deps: Deps, addr: &str) -> bool {
+    deps.querier
+        .query::<ContractInfoResponse>(
+            &WasmQuery::ContractInfo {
+                contract_addr: addr.to_string(),
+            }
+            .into(),
+        )
+        .is_ok()
+}
 
-        let total_contributions = CONTRIBUTIONS
-            .range(deps.storage, None, None, Order::Ascending)
-            .map(|item| item.unwrap().1)
-            .fold(Uint128::zero(), |acc, x| acc + x);
+/// Index of the action sent with reply id `id`, if it is one.
+fn action_index(storage: &dyn Storage, id: u64) -> StdResult<Option<u32>> {
+    let first = match ACTION_REPLIES.may_load(storage)? {
+        Some(first) if id >= first => first,
+        _ => return Ok(None),
+    };
+    let count = ACTIONS.may_load(storage)?.unwrap_or_default().len() as u64;
+    Ok((id - first < count).then(|| (id - first) as u32))
+}
 
-        if total_contributions < threshold_coin.amount {
-            // refund all contributions
-            let mut res = Response::new()
-                .add_attribute("method", "resolve")
-                .add_attribute("status", "refund");
+/// Adds `coins` to what is owed to `recipient` after a failed payout.
+fn owe(storage: &mut dyn Storage, recipient: &str, coins: &[Coin]) -> StdResult<()> {
+    let mut owed = NativeBalance(
+        FAILED_PAYOUTS
+            .may_load(storage, recipient)?
+            .unwrap_or_default(),
+    );
+    for coin in coins.iter().cloned() {
+        owed += coin;
+    }
+    owed.normalize();
+    FAILED_PAYOUTS.save(storage, recipient, &owed.into_vec())
+}
 
-            for item in CONTRIBUTIONS
-                .range(deps.storage, None, None, Order::Ascending)
-                .map(|item| item.unwrap())
-            {
-                let (addr, amount) = item;
-                res = res.add_message(CosmosMsg::Bank(BankMsg::Send {
-                    to_address: addr.to_string(),
-                    amount: vec![Coin {
-                        denom: threshold_coin.denom.clone(),
-                        amount,
-                    }],
-                }));
-            }
+fn next_reply_id(storage: &mut dyn Storage) -> StdResult<u64> {
+    let id = REPLY_COUNT.may_load(storage)?.unwrap_or_default();
+    REPLY_COUNT.save(storage, &(id + 1))?;
+    Ok(id)
+}
 
-            Ok(res)
-        } else {
-            // send all funds to the receiver
-            Ok(Response::new()
-                .add_attribute("method", "resolve")
-                .add_attribute("status", "send")
-                .add_message(CosmosMsg::Bank(BankMsg::Send {
-                    to_address: receiver,
-                    amount: vec![Coin {
-                        denom: threshold_coin.denom,
-                        amount: total_contributions,
-                    }],
-                })))
+/// Actions are run by the campaign itself: they are bank sends and wasm messages that must not
+/// call it back, and can only spend accepted native denoms. The cw20 token is left alone, all of it goes to the receivers.
+fn validate_actions(deps: Deps, env: &Env, actions: &[CosmosMsg]) -> Result<(), ContractError> {
+    if actions.len() > MAX_ACTIONS {
+        return Err(ContractError::InvalidAction {
+            reason: format!("at most {} actions", MAX_ACTIONS),
+        });
+    }
+    let token = CW20_TOKEN.may_load(deps.storage)?;
+    for action in actions {
+        // only messages whose funds `action_funds` counts, anything else could spend the pot
+        // behind the receivers' back
+        let contract_addr = match action {
+            CosmosMsg::Bank(BankMsg::Send { .. })
+            | CosmosMsg::Wasm(WasmMsg::Instantiate { .. }) => continue,
+            CosmosMsg::Wasm(
+                WasmMsg::Execute { contract_addr, .. }
+                | WasmMsg::Migrate { contract_addr, .. }
+                | WasmMsg::UpdateAdmin { contract_addr, .. }
+                | WasmMsg::ClearAdmin { contract_addr },
+            ) => contract_addr,
+            _ => {
+                return Err(ContractError::InvalidAction {
+                    reason: "only bank sends and wasm messages can be run".to_string(),
+                })
+            }
+        };
+        if *contract_addr == env.contract.address {
+            return Err(ContractError::InvalidAction {
+                reason: "the campaign cannot target itself".to_string(),
+            });
+        }
+        if matches!(&token, Some(token) if token == contract_addr) {
+            return Err(ContractError::InvalidAction {
+                reason: "the cw20 token is paid to the receivers".to_string(),
+            });
         }
     }
+    for coin in action_funds(actions) {
+        if !DENOM_WEIGHTS.has(deps.storage, &coin.denom) {
+            return Err(ContractError::InvalidAction {
+                reason: format!("{} is not collected", coin.denom),
+            });
+        }
+    }
+    Ok(())
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetUserContribution { addr } => {
-            to_binary(&query::usercontribution(deps, env, addr)?)
+/// Shares must be positive, distinct and sum to `TOTAL_BPS`.
+fn validate_receivers(deps: Deps, receivers: &[ReceiverShare]) -> Result<(), ContractError> {
+    let mut total = 0u32;
+    for (i, receiver) in receivers.iter().enumerate() {
+        deps.api.addr_validate(&receiver.address)?;
+        if receiver.share_bps == 0 {
+            return Err(ContractError::InvalidReceivers {
+                reason: format!("{} has no share", receiver.address),
+            });
         }
-        QueryMsg::GetTotalContribution {} => to_binary(&query::totalcontribution(deps, env)?),
-        QueryMsg::GetDeadline {} => to_binary(&query::deadline(deps, env)?),
+        if receivers[..i]
+            .iter()
+            .any(|other| other.address == receiver.address)
+        {
+            return Err(ContractError::InvalidReceivers {
+                reason: format!("{} is listed twice", receiver.address),
+            });
+        }
+        total += u32::from(receiver.share_bps);
     }
+    if total != u32::from(TOTAL_BPS) {
+        return Err(ContractError::InvalidReceivers {
+            reason: format!("shares sum to {} bps instead of {}", total, TOTAL_BPS),
+        });
+    }
+    Ok(())
 }
 
-pub mod query {
-    use super::*;
+/// A guardian pauses at least once and at most `MAX_PAUSES` times, each pause lasting at
+/// most 30 days.
+fn validate_guardian(guardian: &GuardianConfig) -> Result<(), ContractError> {
+    if guardian.max_pauses == 0 || guardian.max_pauses > MAX_PAUSES {
+        return Err(ContractError::InvalidGuardian {
+            reason: format!("max_pauses must be between 1 and {}", MAX_PAUSES),
+        });
+    }
+    let (length, max) = match guardian.max_pause {
+        Duration::Height(blocks) => (blocks, MAX_PAUSE_BLOCKS),
+        Duration::Time(seconds) => (seconds, MAX_PAUSE_SECONDS),
+    };
+    if length == 0 || length > max {
+        return Err(ContractError::InvalidGuardian {
+            reason: format!("max_pause must be between 1 and {}", max),
+        });
+    }
+    Ok(())
+}
 
-    // TBD why option and not just the unboxed type?
+/// Recipient and the coins it is paid.
+type Share = (String, Vec<Coin>);
 
-    #[cw_serde]
-    pub struct ContributionResponse {
-        pub amount: Uint128,
+/// Splits `coins` between the receivers by share. Rounding dust goes to the first receiver.
+fn split(storage: &dyn Storage, coins: &[Coin]) -> StdResult<Vec<Share>> {
+    let receivers = RECEIVERS.load(storage)?;
+    let mut parts: Vec<Share> = receivers
+        .iter()
+        .map(|receiver| {
+            let share = coins
+                .iter()
+                .map(|coin| Coin {
+                    denom: coin.denom.clone(),
+                    amount: coin.amount.multiply_ratio(receiver.share_bps, TOTAL_BPS),
+                })
+                .collect();
+            (receiver.address.clone(), share)
+        })
+        .collect();
+    for (i, coin) in coins.iter().enumerate() {
+        let paid: Uint128 = parts.iter().map(|(_, share)| share[i].amount).sum();
+        parts[0].1[i].amount += coin.amount - paid;
     }
+    Ok(parts)
+}
 
-    #[cw_serde]
-    pub struct DeadlineResponse {
-        pub timestamp: Timestamp,
+/// Takes the platform fee out of `coins`, returning the treasury with its cut and the rest.
+fn fee_cut(storage: &dyn Storage, coins: Vec<Coin>) -> StdResult<(Option<Share>, Vec<Coin>)> {
+    let fee = match FEE.may_load(storage)? {
+        Some(fee) => fee,
+        None => return Ok((None, coins)),
+    };
+    let mut cut = vec![];
+    let mut rest = vec![];
+    for coin in coins {
+        let amount = coin.amount.multiply_ratio(fee.fee_bps, TOTAL_BPS);
+        cut.push(Coin {
+            denom: coin.denom.clone(),
+            amount,
+        });
+        rest.push(Coin {
+            denom: coin.denom,
+            amount: coin.amount - amount,
+        });
     }
+    Ok((Some((fee.treasury, cut)), rest))
+}
 
-    pub fn usercontribution(
-        deps: Deps,
-        _env: Env,
-        addr: String,
-    ) -> StdResult<ContributionResponse> {
-        let user = deps.api.addr_validate(&addr)?;
-        dbg!(user.clone());
-        if let Some(contribution) = CONTRIBUTIONS.may_load(deps.storage, &user)? {
-            Ok(ContributionResponse {
-                amount: contribution,
-            })
-        } else {
-            Ok(ContributionResponse {
-                amount: Uint128::zero(), // TBD or we could do None
-            })
+/// Native coins spent by `actions`, taken from the collected funds.
+fn action_funds(actions: &[CosmosMsg]) -> Vec<Coin> {
+    let mut spent = NativeBalance::default();
+    for action in actions {
+        let coins = match action {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount,
+            CosmosMsg::Wasm(WasmMsg::Execute { funds, .. })
+            | CosmosMsg::Wasm(WasmMsg::Instantiate { funds, .. }) => funds,
+            _ => continue,
+        };
+        for coin in coins {
+            spent += coin.clone();
         }
     }
+    spent.into_vec()
+}
 
-    pub fn totalcontribution(deps: Deps, _env: Env) -> StdResult<ContributionResponse> {
-        let contributions = CONTRIBUTIONS
-            .range(deps.storage, None, None, Order::Ascending)
-            .map(|item| item.map(|(_, v)| v).unwrap())
-            .fold(Uint128::zero(), |acc, item| acc + item);
-        Ok(ContributionResponse {
-            amount: contributions,
+/// Value of `amount` of `denom` in the threshold's denom.
+fn value(storage: &dyn Storage, denom: &str, amount: Uint128) -> StdResult<Uint128> {
+    Ok(amount * DENOM_WEIGHTS.load(storage, denom)?)
+}
+
+/// Coins pledged by `user`, one per denom.
+fn user_coins(storage: &dyn Storage, user: &Addr) -> StdResult<Vec<Coin>> {
+    CONTRIBUTIONS
+        .prefix(user)
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect()
+}
+
+/// Value of everything pledged by `user`.
+fn user_value(storage: &dyn Storage, user: &Addr) -> StdResult<Uint128> {
+    user_coins(storage, user)?
+        .into_iter()
+        .try_fold(Uint128::zero(), |acc, coin| {
+            Ok(acc + value(storage, &coin.denom, coin.amount)?)
         })
-    }
+}
 
-    pub fn deadline(deps: Deps, _env: Env) -> StdResult<DeadlineResponse> {
-        let timestamp = DEADLINE.may_load(deps.storage)?.unwrap();
-        Ok(DeadlineResponse { timestamp })
+/// Coins pledged per contributor, in storage order: addresses are length-prefixed in the
+/// keys, so shorter ones come first and the order is not alphabetical.
+fn pledges(storage: &dyn Storage) -> StdResult<Vec<(Addr, Vec<Coin>)>> {
+    let mut pledges: Vec<(Addr, Vec<Coin>)> = vec![];
+    for item in CONTRIBUTIONS.range(storage, None, None, Order::Ascending) {
+        let ((addr, denom), amount) = item?;
+        let coin = Coin { denom, amount };
+        match pledges.last_mut() {
+            Some((last, coins)) if *last == addr => coins.push(coin),
+            _ => pledges.push((addr, vec![coin])),
+        }
     }
+    Ok(pledges)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::contract::query::ContributionResponse;
-    use cosmwasm_std::{
-        testing::{mock_dependencies, mock_env, mock_info},
-        Addr, Empty,
+/// Native funds relayers fronted for `user`'s pledge in `denom`.
+fn fronted(storage: &dyn Storage, user: &Addr, denom: &str) -> StdResult<Uint128> {
+    FRONTED
+        .prefix((user, denom))
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, amount)| amount))
+        .sum()
+}
+
+/// Splits `coins` given back from `user`'s pledge between the relayers who fronted part of
+/// it, repaid first, and `user`.
+fn reclaim(storage: &mut dyn Storage, user: &Addr, coins: Vec<Coin>) -> StdResult<Vec<Share>> {
+    let mut shares: Vec<Share> = vec![];
+    let mut credit = |to: String, coin: Coin| match shares.iter_mut().find(|(addr, _)| *addr == to)
+    {
+        Some((_, coins)) => coins.push(coin),
+        None => shares.push((to, vec![coin])),
     };
-    use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+    for mut coin in coins {
+        let relayers = FRONTED
+            .prefix((user, &coin.denom))
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (relayer, amount) in relayers {
+            let repaid = amount.min(coin.amount);
+            if repaid.is_zero() {
+                break;
+            }
+            let key = (user, coin.denom.as_str(), &relayer);
+            if repaid == amount {
+                FRONTED.remove(storage, key);
+            } else {
+                FRONTED.save(storage, key, &(amount - repaid))?;
+            }
+            coin.amount -= repaid;
+            credit(
+                relayer.into_string(),
+                Coin {
+                    denom: coin.denom.clone(),
+                    amount: repaid,
+                },
+            );
+        }
+        if !coin.amount.is_zero() {
+            credit(user.to_string(), coin);
+        }
+    }
+    Ok(shares)
+}
 
-    fn contract() -> Box<dyn Contract<Empty>> {
-        let contract = ContractWrapper::new(execute, instantiate, query);
-        Box::new(contract)
+/// Refund taxes kept in the pot, one coin per denom.
+fn taxes(storage: &dyn Storage) -> StdResult<Vec<Coin>> {
+    REFUND_TAXES
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect()
+}
+
+/// Payouts of the refund taxes, which belong to nobody who pledged, to the receivers. A
+/// campaign still pending has neither taxes nor receivers.
+fn tax_payouts(storage: &mut dyn Storage) -> StdResult<Vec<SubMsg>> {
+    let taxes = taxes(storage)?;
+    if taxes.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut submsgs = vec![];
+    for (receiver, coins) in split(storage, &taxes)? {
+        submsgs.extend(payouts(storage, &receiver, coins, None)?);
     }
+    Ok(submsgs)
+}
 
-    fn contract_builder(deadline: Timestamp) -> (App, Addr) {
-        let mut app = App::default();
-        let contract_id = app.store_code(contract());
-        let contract_addr = app
-            .instantiate_contract(
-                contract_id,
-                Addr::unchecked("sender"),
-                &InstantiateMsg {
-                    coin_threshold: Coin {
-                        denom: "OSMO".to_string(),
-                        amount: Uint128::from(10_000_000u128),
-                    },
-                    deadline: deadline,
-                    receiver: None,
-                },
-                &[],
-                "Threshold Funding",
-                None,
-            )
-            .unwrap();
-        (app, contract_addr)
+/// Everything held by the campaign: contributions and refund taxes, one coin per denom.
+fn pot_coins(storage: &dyn Storage) -> StdResult<Vec<Coin>> {
+    let mut totals: BTreeMap<String, Uint128> = BTreeMap::new();
+    for item in CONTRIBUTIONS.range(storage, None, None, Order::Ascending) {
+        let ((_, denom), amount) = item?;
+        *totals.entry(denom).or_default() += amount;
     }
+    for coin in taxes(storage)? {
+        *totals.entry(coin.denom).or_default() += coin.amount;
+    }
+    Ok(totals
+        .into_iter()
+        .map(|(denom, amount)| Coin { denom, amount })
+        .collect())
+}
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies();
+fn total_contributions(storage: &dyn Storage) -> StdResult<Uint128> {
+    CONTRIBUTIONS
+        .range(storage, None, None, Order::Ascending)
+        .try_fold(Uint128::zero(), |acc, item| {
+            let ((_, denom), amount) = item?;
+            Ok(acc + value(storage, &denom, amount)?)
+        })
+}
 
-        let msg_no_receiver = InstantiateMsg {
-            coin_threshold: Coin {
-                denom: "OSMO".to_string(),
-                amount: Uint128::from(10_000_000u128),
-            },
-            deadline: Timestamp::from_seconds(10),
-            receiver: None,
-        };
-        let info = mock_info("creator", &[]);
+/// Value of everything counting towards the threshold: contributions and refund taxes.
+fn pot(storage: &dyn Storage) -> StdResult<Uint128> {
+    taxes(storage)?
+        .into_iter()
+        .try_fold(total_contributions(storage)?, |acc, coin| {
+            Ok(acc + value(storage, &coin.denom, coin.amount)?)
+        })
+}
 
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg_no_receiver).unwrap();
-        assert_eq!(0, res.messages.len());
-        assert_eq!(5, res.attributes.len());
+/// Coins as a comma separated list, "0" if there are none.
+fn coins_to_string(coins: &[Coin]) -> String {
+    if coins.is_empty() {
+        return "0".to_string();
+    }
+    coins
+        .iter()
+        .map(|coin| coin.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Contributions and refunds are accepted while the deadline has not expired,
+/// resolution only once it has.
+fn ensure_not_expired(deps: Deps, env: &Env) -> Result<(), ContractError> {
+    ensure_unsettled(deps.storage)?;
+    if load_deadline(deps, env)?.is_expired(&env.block) {
+        return Err(ContractError::DeadlinePassed {});
+    }
+    Ok(())
+}
+
+/// The campaign has been neither resolved nor cancelled.
+fn ensure_unsettled(storage: &dyn Storage) -> Result<(), ContractError> {
+    match OUTCOME.may_load(storage)? {
+        Some(Outcome::Resolved {}) => Err(ContractError::AlreadyResolved {}),
+        Some(Outcome::Cancelled { .. }) => Err(ContractError::Cancelled {}),
+        None => Ok(()),
+    }
+}
+
+/// Privileged operations are reserved to the owner, and to nobody once it renounced.
+fn ensure_owner(storage: &dyn Storage, sender: &Addr) -> Result<(), ContractError> {
+    match OWNER.may_load(storage)? {
+        Some(owner) if owner == *sender => Ok(()),
+        _ => Err(ContractError::Unauthorized {}),
+    }
+}
+
+fn ensure_guardian(storage: &dyn Storage, sender: &Addr) -> Result<GuardianConfig, ContractError> {
+    match GUARDIAN.may_load(storage)? {
+        Some(guardian) if guardian.address == sender.as_str() => Ok(guardian),
+        _ => Err(ContractError::Unauthorized {}),
+    }
+}
+
+/// Latest pause and its id, if it is still going on.
+fn current_pause(storage: &dyn Storage, env: &Env) -> StdResult<Option<(u32, Pause)>> {
+    let id = PAUSE_COUNT.may_load(storage)?.unwrap_or_default();
+    Ok(PAUSES
+        .may_load(storage, id)?
+        .filter(|pause| pause.is_active(&env.block))
+        .map(|pause| (id, pause)))
+}
+
+/// Contributions and resolution are suspended during a pause, refunds never are.
+fn ensure_not_paused(storage: &dyn Storage, env: &Env) -> Result<(), ContractError> {
+    if current_pause(storage, env)?.is_some() {
+        return Err(ContractError::Paused {});
+    }
+    Ok(())
+}
+
+fn validate_root(root: &Binary) -> Result<(), ContractError> {
+    if root.len() != 32 {
+        return Err(ContractError::InvalidMerkleRoot {});
+    }
+    Ok(())
+}
+
+/// With an allowlist, only listed addresses may pledge, returning the address' cap if any.
+/// A verified proof is remembered, later pledges under the same root need none.
+fn allowlist_cap(
+    storage: &mut dyn Storage,
+    user: &Addr,
+    proof: Option<AllowlistProof>,
+) -> Result<Option<Uint128>, ContractError> {
+    let root = match ALLOWLIST_ROOT.may_load(storage)? {
+        Some(root) => root,
+        None => return Ok(None),
+    };
+    if let Some(proof) = proof {
+        let path = proof
+            .proof
+            .iter()
+            .map(|node| Hash::try_from(node.as_slice()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ContractError::NotAllowlisted {})?;
+        if !merkle::verify(&root, merkle::leaf(user.as_str(), proof.cap), &path) {
+            return Err(ContractError::NotAllowlisted {});
+        }
+        ALLOWLISTED.save(
+            storage,
+            user,
+            &AllowlistEntry {
+                root,
+                cap: proof.cap,
+            },
+        )?;
+        return Ok(proof.cap);
+    }
+    match ALLOWLISTED.may_load(storage, user)? {
+        Some(entry) if entry.root == root => Ok(entry.cap),
+        _ => Err(ContractError::NotAllowlisted {}),
+    }
+}
+
+/// With an attester, only contributors holding a valid credential may pledge, up to its
+/// `max_amount`. A new credential replaces the stored one.
+fn credential_cap(
+    deps: DepsMut,
+    env: &Env,
+    user: &Addr,
+    signed: Option<SignedCredential>,
+) -> Result<Option<Uint128>, ContractError> {
+    let attester = match ATTESTER.may_load(deps.storage)? {
+        Some(attester) => attester,
+        None => return Ok(None),
+    };
+    let signed = match signed {
+        Some(signed) => signed,
+        None => {
+            let credential = CREDENTIALS
+                .may_load(deps.storage, user)?
+                .ok_or(ContractError::NotEligible {})?;
+            ensure_credential_valid(deps.storage, env, &credential)?;
+            return Ok(Some(credential.max_amount));
+        }
+    };
+
+    let credential = signed.credential;
+    let invalid = |reason: &str| ContractError::InvalidCredential {
+        reason: reason.to_string(),
+    };
+    if credential.address != user.as_str() {
+        return Err(invalid("issued to another address"));
+    }
+    if USED_NONCES.has(deps.storage, credential.nonce) {
+        return Err(invalid("already used"));
+    }
+    let digest = credential::digest(env.contract.address.as_str(), &credential)?;
+    let verified = deps
+        .api
+        .secp256k1_verify(&digest, &signed.signature, &attester)
+        .map_err(|err| invalid(&err.to_string()))?;
+    if !verified {
+        return Err(invalid("not signed by the attester"));
+    }
+    ensure_credential_valid(deps.storage, env, &credential)?;
+    USED_NONCES.save(deps.storage, credential.nonce, user)?;
+    CREDENTIALS.save(deps.storage, user, &credential)?;
+    Ok(Some(credential.max_amount))
+}
+
+fn ensure_credential_valid(
+    storage: &dyn Storage,
+    env: &Env,
+    credential: &Credential,
+) -> Result<(), ContractError> {
+    let reason = if credential.expiry <= env.block.time {
+        "expired"
+    } else if REVOKED_NONCES.has(storage, credential.nonce) {
+        "revoked"
+    } else {
+        return Ok(());
+    };
+    Err(ContractError::InvalidCredential {
+        reason: reason.to_string(),
+    })
+}
+
+/// Value of everything `user` pledged, contributions and allowance pledge.
+fn committed(storage: &dyn Storage, user: &Addr) -> StdResult<Uint128> {
+    let mut committed = user_value(storage, user)?;
+    if let Some(token) = CW20_TOKEN.may_load(storage)? {
+        let pledged = ALLOWANCE_PLEDGES
+            .may_load(storage, user)?
+            .unwrap_or_default();
+        committed += value(storage, token.as_str(), pledged)?;
+    }
+    Ok(committed)
+}
+
+/// Lowest of two optional caps.
+fn tightest(a: Option<Uint128>, b: Option<Uint128>) -> Option<Uint128> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn is_cancelled(storage: &dyn Storage) -> StdResult<bool> {
+    Ok(matches!(
+        OUTCOME.may_load(storage)?,
+        Some(Outcome::Cancelled { .. })
+    ))
+}
+
+/// Contributions are accepted between the opening and the deadline.
+fn ensure_open(deps: Deps, env: &Env) -> Result<(), ContractError> {
+    ensure_not_expired(deps, env)?;
+    ensure_not_paused(deps.storage, env)?;
+    if RECEIVERS.may_load(deps.storage)?.is_none() {
+        return Err(ContractError::Pending {});
+    }
+    if let Some(opening) = OPENING.may_load(deps.storage)? {
+        if !opening.is_triggered(&env.block) {
+            return Err(ContractError::NotOpen {});
+        }
+    }
+    Ok(())
+}
+
+/// Value of the allowance pledges, which count towards the threshold only if honoured.
+fn allowance_total(storage: &dyn Storage) -> StdResult<Uint128> {
+    let token = match CW20_TOKEN.may_load(storage)? {
+        Some(token) => token,
+        None => return Ok(Uint128::zero()),
+    };
+    let pledged = ALLOWANCE_PLEDGES
+        .range(storage, None, None, Order::Ascending)
+        .try_fold(Uint128::zero(), |acc, item| -> StdResult<_> {
+            Ok(acc + item?.1)
+        })?;
+    value(storage, token.as_str(), pledged)
+}
+
+type AllowancePledges = Vec<(Addr, Uint128)>;
+
+/// Splits the allowance pledges between those the contract can currently pull, and those
+/// whose allowance or balance fell short.
+fn check_pledges(deps: Deps, env: &Env) -> StdResult<(AllowancePledges, AllowancePledges)> {
+    let token = match CW20_TOKEN.may_load(deps.storage)? {
+        Some(token) => token,
+        None => return Ok((vec![], vec![])),
+    };
+    let mut honoured = vec![];
+    let mut defaulted = vec![];
+    for item in ALLOWANCE_PLEDGES.range(deps.storage, None, None, Order::Ascending) {
+        let (owner, amount) = item?;
+        let allowance: AllowanceResponse = deps.querier.query_wasm_smart(
+            &token,
+            &Cw20QueryMsg::Allowance {
+                owner: owner.to_string(),
+                spender: env.contract.address.to_string(),
+            },
+        )?;
+        let balance: BalanceResponse = deps.querier.query_wasm_smart(
+            &token,
+            &Cw20QueryMsg::Balance {
+                address: owner.to_string(),
+            },
+        )?;
+        if allowance.expires.is_expired(&env.block)
+            || allowance.allowance < amount
+            || balance.balance < amount
+        {
+            defaulted.push((owner, amount));
+        } else {
+            honoured.push((owner, amount));
+        }
+    }
+    Ok((honoured, defaulted))
+}
+
+pub mod execute {
+    use super::*;
+
+    /// TBD spec
+    pub fn contribution(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        coin: Coin,
+        allowlist: Option<AllowlistProof>,
+        credential: Option<SignedCredential>,
+        beneficiary: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let user = deps
+            .api
+            .addr_validate(beneficiary.as_deref().unwrap_or(info.sender.as_str()))?;
+
+        if info.funds.len() != 1 || info.funds[0].denom != coin.denom {
+            return Err(ContractError::CustomError { val: String::new() });
+        }
+
+        // verify that the amount is the same as the ContributionMsg's amount
+        if info.funds[0].amount != coin.amount {
+            return Err(ContractError::CustomError { val: String::new() });
+            // TODO you can trigger a refund
+        }
+
+        let res = pledge(deps, env, user, coin, allowlist, credential)?;
+        Ok(match beneficiary {
+            Some(_) => res.add_attribute("funder", info.sender),
+            None => res,
+        })
+    }
+
+    /// one payment pledging for several beneficiaries, who must be able to pledge
+    /// without proof or credential
+    pub fn contribution_batch(
+        mut deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        contributions: Vec<BeneficiaryContribution>,
+    ) -> Result<Response, ContractError> {
+        // checked upfront, a zero coin would vanish from the funds comparison below
+        for (i, contribution) in contributions.iter().enumerate() {
+            if contribution.coin.amount.is_zero() {
+                return Err(ContractError::CustomError {
+                    val: format!("nothing pledged for {}", contribution.beneficiary),
+                });
+            }
+            if contributions[..i]
+                .iter()
+                .any(|other| other.beneficiary == contribution.beneficiary)
+            {
+                return Err(ContractError::CustomError {
+                    val: format!("{} is listed twice", contribution.beneficiary),
+                });
+            }
+        }
+        let mut owed = NativeBalance(
+            contributions
+                .iter()
+                .map(|contribution| contribution.coin.clone())
+                .collect(),
+        );
+        owed.normalize();
+        let mut sent = NativeBalance(info.funds.clone());
+        sent.normalize();
+        if contributions.is_empty() || owed != sent {
+            return Err(ContractError::CustomError {
+                val: "funds do not match the contributions".to_string(),
+            });
+        }
+
+        let mut res = Response::new()
+            .add_attribute("method", "contribution-batch")
+            .add_attribute("funder", info.sender);
+        for contribution in contributions {
+            let user = deps.api.addr_validate(&contribution.beneficiary)?;
+            let pledged = pledge(
+                deps.branch(),
+                env.clone(),
+                user,
+                contribution.coin,
+                None,
+                None,
+            )?;
+            res = res.add_attributes(
+                pledged
+                    .attributes
+                    .into_iter()
+                    .filter(|attr| attr.key != "method"),
+            );
+        }
+        Ok(res)
+    }
+
+    /// cw20 tokens sent by the accepted token contract on behalf of a contributor
+    pub fn receive(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: Cw20ReceiveMsg,
+    ) -> Result<Response, ContractError> {
+        match CW20_TOKEN.may_load(deps.storage)? {
+            Some(token) if token == info.sender => {}
+            _ => {
+                return Err(ContractError::UnacceptedDenom {
+                    denom: info.sender.into_string(),
+                })
+            }
+        }
+
+        match from_binary(&msg.msg)? {
+            ReceiveMsg::Contribute {
+                allowlist,
+                credential,
+            } => {
+                let user = deps.api.addr_validate(&msg.sender)?;
+                let coin = Coin {
+                    denom: info.sender.into_string(),
+                    amount: msg.amount,
+                };
+                pledge(deps, env, user, coin, allowlist, credential)
+            }
+        }
+    }
+
+    /// Records `coin` as part of `user`'s pledge, once the funds are in.
+    fn pledge(
+        mut deps: DepsMut,
+        env: Env,
+        user: Addr,
+        coin: Coin,
+        allowlist: Option<AllowlistProof>,
+        credential: Option<SignedCredential>,
+    ) -> Result<Response, ContractError> {
+        ensure_open(deps.as_ref(), &env)?;
+        let cap = tightest(
+            allowlist_cap(deps.storage, &user, allowlist)?,
+            credential_cap(deps.branch(), &env, &user, credential)?,
+        );
+
+        weight(deps.storage, &coin.denom)?;
+        refresh_prices(deps.branch(), &env, Some(&coin.denom))?;
+        mark_late(deps.branch(), &env, &user)?;
+
+        let amount = coin.amount;
+        CONTRIBUTIONS.update(
+            deps.storage,
+            (&user, &coin.denom),
+            |old| -> StdResult<Uint128> {
+                match old {
+                    Some(old) => Ok(old + amount),
+                    None => Ok(amount),
+                }
+            },
+        )?;
+        if let Some(min) = MIN_CONTRIBUTION.may_load(deps.storage)? {
+            if user_value(deps.storage, &user)? < min {
+                return Err(ContractError::ContributionTooLow {});
+            }
+        }
+        if matches!(cap, Some(cap) if committed(deps.storage, &user)? > cap) {
+            return Err(ContractError::AbovePledgeCap {});
+        }
+
+        let mut res = Response::new()
+            .add_attribute("method", "contribution")
+            .add_attribute("contributor", user)
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", coin.denom);
+        if let Some(deadline) = extend_if_late(deps.branch(), &env)? {
+            res = res.add_attribute("deadline-extended", deadline.to_string());
+        }
+        Ok(res)
+    }
+
+    /// register a pledge of cw20 tokens that stay with the contributor,
+    /// pulled through the allowance only if the campaign succeeds
+    pub fn pledge_allowance(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        amount: Uint128,
+        allowlist: Option<AllowlistProof>,
+        credential: Option<SignedCredential>,
+    ) -> Result<Response, ContractError> {
+        pledge_from_allowance(deps, env, info.sender, amount, allowlist, credential)
+    }
+
+    fn pledge_from_allowance(
+        mut deps: DepsMut,
+        env: Env,
+        owner: Addr,
+        amount: Uint128,
+        allowlist: Option<AllowlistProof>,
+        credential: Option<SignedCredential>,
+    ) -> Result<Response, ContractError> {
+        ensure_open(deps.as_ref(), &env)?;
+        let cap = tightest(
+            allowlist_cap(deps.storage, &owner, allowlist)?,
+            credential_cap(deps.branch(), &env, &owner, credential)?,
+        );
+        let token = CW20_TOKEN
+            .may_load(deps.storage)?
+            .ok_or(ContractError::NoCw20Token {})?;
+        refresh_prices(deps.branch(), &env, Some(token.as_str()))?;
+
+        let pledged = ALLOWANCE_PLEDGES
+            .may_load(deps.storage, &owner)?
+            .unwrap_or_default()
+            + amount;
+        let allowance: AllowanceResponse = deps.querier.query_wasm_smart(
+            &token,
+            &Cw20QueryMsg::Allowance {
+                owner: owner.to_string(),
+                spender: env.contract.address.to_string(),
+            },
+        )?;
+        if allowance.expires.is_expired(&env.block) || allowance.allowance < pledged {
+            return Err(ContractError::InsufficientAllowance {});
+        }
+        let total =
+            user_value(deps.storage, &owner)? + value(deps.storage, token.as_str(), pledged)?;
+        match MIN_CONTRIBUTION.may_load(deps.storage)? {
+            Some(min) if total < min => return Err(ContractError::ContributionTooLow {}),
+            _ if amount.is_zero() => return Err(ContractError::ContributionTooLow {}),
+            _ => {}
+        }
+        if matches!(cap, Some(cap) if total > cap) {
+            return Err(ContractError::AbovePledgeCap {});
+        }
+        ALLOWANCE_PLEDGES.save(deps.storage, &owner, &pledged)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "pledge-allowance")
+            .add_attribute("contributor", owner)
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("pledged", pledged.to_string()))
+    }
+
+    /// hand part of a pledge over to another address, only valid while contributions are open
+    /// and for what the sender paid itself
+    pub fn transfer_pledge(
+        mut deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        to: String,
+        amount: Coin,
+        allowlist: Option<AllowlistProof>,
+        credential: Option<SignedCredential>,
+    ) -> Result<Response, ContractError> {
+        ensure_open(deps.as_ref(), &env)?;
+        let to = deps.api.addr_validate(&to)?;
+        let key = (&info.sender, amount.denom.as_str());
+        let pledged = CONTRIBUTIONS
+            .may_load(deps.storage, key)?
+            .unwrap_or_default();
+        // what relayers fronted stays with the pledge, to be repaid to them on a refund
+        let movable = pledged
+            .checked_sub(fronted(deps.storage, &info.sender, &amount.denom)?)
+            .unwrap_or_default();
+        if amount.amount.is_zero() || amount.amount > movable || to == info.sender {
+            return Err(ContractError::InvalidTransfer {});
+        }
+        let cap = tightest(
+            allowlist_cap(deps.storage, &to, allowlist)?,
+            credential_cap(deps.branch(), &env, &to, credential)?,
+        );
+        refresh_prices(deps.branch(), &env, Some(&amount.denom))?;
+
+        let moved = value(deps.storage, &amount.denom, amount.amount)?;
+        if let Some(min) = MIN_CONTRIBUTION.may_load(deps.storage)? {
+            let remaining = user_value(deps.storage, &info.sender)? - moved;
+            let received = user_value(deps.storage, &to)? + moved;
+            if (!remaining.is_zero() && remaining < min) || received < min {
+                return Err(ContractError::ContributionTooLow {});
+            }
+        }
+        if matches!(cap, Some(cap) if committed(deps.storage, &to)? + moved > cap) {
+            return Err(ContractError::AbovePledgeCap {});
+        }
+        if pledged == amount.amount {
+            CONTRIBUTIONS.remove(deps.storage, key);
+        } else {
+            CONTRIBUTIONS.save(deps.storage, key, &(pledged - amount.amount))?;
+        }
+        CONTRIBUTIONS.update(
+            deps.storage,
+            (&to, &amount.denom),
+            |old| -> StdResult<Uint128> { Ok(old.unwrap_or_default() + amount.amount) },
+        )?;
+        mark_late(deps.branch(), &env, &to)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "transfer-pledge")
+            .add_attribute("from", info.sender)
+            .add_attribute("to", to)
+            .add_attribute("amount", amount.to_string()))
+    }
+
+    /// relayer submits a pledge signed by the contributor, fronting native funds
+    /// or drawing on the contributor's cw20 allowance
+    pub fn submit_intent(
+        mut deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        signed: SignedIntent,
+    ) -> Result<Response, ContractError> {
+        let SignedIntent {
+            intent,
+            pubkey,
+            signature,
+        } = signed;
+        let invalid = |reason: &str| ContractError::InvalidIntent {
+            reason: reason.to_string(),
+        };
+        if intent.campaign != env.contract.address.as_str() {
+            return Err(invalid("meant for another campaign"));
+        }
+        if intent.expiry <= env.block.time {
+            return Err(invalid("expired"));
+        }
+        let last = INTENT_NONCES.may_load(deps.storage, &pubkey)?;
+        if last.is_some_and(|last| intent.nonce <= last) {
+            return Err(invalid("nonce already used"));
+        }
+        let digest = intent::digest(&intent)?;
+        let verified = deps
+            .api
+            .secp256k1_verify(&digest, &signature, &pubkey)
+            .map_err(|err| invalid(&err.to_string()))?;
+        if !verified {
+            return Err(invalid("not signed by the key"));
+        }
+        // only the key's own account can be committed by its signature
+        let contributor =
+            intent::contributor(deps.api, &pubkey).map_err(|err| invalid(&err.to_string()))?;
+
+        let cw20 = CW20_TOKEN
+            .may_load(deps.storage)?
+            .is_some_and(|token| token == intent.amount.denom);
+        if cw20 {
+            if !info.funds.is_empty() {
+                return Err(invalid("cw20 pledges are drawn from the allowance"));
+            }
+        } else if info.funds != [intent.amount.clone()] {
+            return Err(invalid("funds do not match the intent"));
+        }
+        INTENT_NONCES.save(deps.storage, &pubkey, &intent.nonce)?;
+
+        let res = if cw20 {
+            pledge_from_allowance(deps, env, contributor, intent.amount.amount, None, None)?
+        } else {
+            let res = pledge(
+                deps.branch(),
+                env,
+                contributor.clone(),
+                intent.amount.clone(),
+                None,
+                None,
+            )?;
+            let key = (&contributor, intent.amount.denom.as_str(), &info.sender);
+            FRONTED.update(deps.storage, key, |old| -> StdResult<_> {
+                Ok(old.unwrap_or_default() + intent.amount.amount)
+            })?;
+            res
+        };
+        Ok(res
+            .add_attribute("relayer", info.sender)
+            .add_attribute("nonce", intent.nonce.to_string()))
+    }
+
+    /// withdraw an allowance pledge, only valid before deadline
+    pub fn cancel_pledge(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        ensure_not_expired(deps.as_ref(), &env)?;
+        let pledged = ALLOWANCE_PLEDGES
+            .may_load(deps.storage, &info.sender)?
+            .ok_or(ContractError::NoContribution {})?;
+        ALLOWANCE_PLEDGES.remove(deps.storage, &info.sender);
+
+        Ok(Response::new()
+            .add_attribute("method", "cancel-pledge")
+            .add_attribute("contributor", info.sender)
+            .add_attribute("amount", pledged.to_string()))
+    }
+
+    // refund a single user's contribution
+    // only valid before deadline
+    pub fn refund(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        _: ExecuteMsg,
+    ) -> Result<Response, ContractError> {
+        let user = deps.api.addr_validate(info.sender.as_ref())?;
+
+        let coins = user_coins(deps.storage, &user)?;
+
+        if coins.is_empty() {
+            return Err(ContractError::CustomError { val: String::new() });
+        }
+
+        take_back(deps, env, user, coins, "refund")
+    }
+
+    // get a contribution back in full once the campaign is cancelled
+    pub fn claim_refund(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        if !is_cancelled(deps.storage)? {
+            return Err(ContractError::NotCancelled {});
+        }
+        let coins = user_coins(deps.storage, &info.sender)?;
+        if coins.is_empty() {
+            return Err(ContractError::NoContribution {});
+        }
+
+        take_back(deps, env, info.sender, coins, "claim-refund")
+    }
+
+    // withdraw part of a single user's contribution in one denom
+    // only valid before deadline
+    pub fn withdraw(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        amount: Uint128,
+        denom: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let user = deps.api.addr_validate(info.sender.as_ref())?;
+        let denom = match denom {
+            Some(denom) => denom,
+            None => THRESHOLD_COIN.load(deps.storage)?.denom,
+        };
+
+        let pledged = CONTRIBUTIONS
+            .may_load(deps.storage, (&user, &denom))?
+            .ok_or(ContractError::NoContribution {})?;
+        if amount.is_zero() || amount > pledged {
+            return Err(ContractError::InvalidWithdrawal {});
+        }
+
+        // what is left must still meet the minimum
+        if let Some(min) = MIN_CONTRIBUTION.may_load(deps.storage)? {
+            let remaining = user_value(deps.storage, &user)? - value(deps.storage, &denom, amount)?;
+            if !remaining.is_zero() && remaining < min {
+                return Err(ContractError::ContributionTooLow {});
+            }
+        }
+
+        take_back(deps, env, user, vec![Coin { denom, amount }], "withdraw")
+    }
+
+    /// Returns `coins` from `user`'s pledge, subject to the refund policy.
+    fn take_back(
+        mut deps: DepsMut,
+        env: Env,
+        user: Addr,
+        coins: Vec<Coin>,
+        method: &str,
+    ) -> Result<Response, ContractError> {
+        // a cancelled campaign gives everything back, whatever the refund policy
+        let cancelled = is_cancelled(deps.storage)?;
+        let tax_rate = if cancelled {
+            Decimal::zero()
+        } else {
+            ensure_not_expired(deps.as_ref(), &env)?;
+            let threshold_reached = pot(deps.storage)? >= current_threshold(deps.as_ref(), &env)?;
+            match REFUND_POLICY.load(deps.storage)? {
+                RefundPolicy::LockOnThreshold {} if threshold_reached => {
+                    return Err(ContractError::RefundsLocked {});
+                }
+                RefundPolicy::TaxOnThreshold { tax } if threshold_reached => tax,
+                _ => Decimal::zero(),
+            }
+        };
+
+        for coin in &coins {
+            let key = (&user, coin.denom.as_str());
+            let pledged = CONTRIBUTIONS.load(deps.storage, key)?;
+            if pledged == coin.amount {
+                CONTRIBUTIONS.remove(deps.storage, key);
+            } else {
+                CONTRIBUTIONS.save(deps.storage, key, &(pledged - coin.amount))?;
+            }
+        }
+
+        // relayers get back what they fronted, taxed like the rest
+        let mut returned = NativeBalance::default();
+        let mut taxed = NativeBalance::default();
+        let mut msgs = vec![];
+        for (to, share) in reclaim(deps.storage, &user, coins)? {
+            let mut paid = vec![];
+            for coin in share {
+                let tax = coin.amount * tax_rate;
+                if !tax.is_zero() {
+                    REFUND_TAXES.update(deps.storage, &coin.denom, |taxes| -> StdResult<_> {
+                        Ok(taxes.unwrap_or_default() + tax)
+                    })?;
+                    taxed += Coin {
+                        denom: coin.denom.clone(),
+                        amount: tax,
+                    };
+                }
+                if coin.amount > tax {
+                    paid.push(Coin {
+                        denom: coin.denom,
+                        amount: coin.amount - tax,
+                    });
+                }
+            }
+            for coin in paid.iter().cloned() {
+                returned += coin;
+            }
+            if !paid.is_empty() {
+                msgs.extend(transfer(deps.storage, &to, paid)?);
+            }
+        }
+        returned.normalize();
+        taxed.normalize();
+
+        let mut res = Response::new()
+            .add_attribute("method", method)
+            .add_attribute("contributor", user.as_str())
+            .add_attribute("amount", coins_to_string(&returned.into_vec()))
+            .add_attribute("tax", coins_to_string(&taxed.into_vec()))
+            .add_messages(msgs);
+        if cancelled {
+            return Ok(res);
+        }
+        if let Some(deadline) = extend_if_late(deps.branch(), &env)? {
+            res = res.add_attribute("deadline-extended", deadline.to_string());
+        }
+        Ok(res)
+    }
+
+    /// owner calls the campaign off, every contribution can then be claimed back
+    pub fn cancel(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        reason: String,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &info.sender)?;
+        ensure_not_expired(deps.as_ref(), &env)?;
+        if let CancelWindow::BeforeSuccess {} = CANCEL_WINDOW.load(deps.storage)? {
+            if pot(deps.storage)? >= current_threshold(deps.as_ref(), &env)? {
+                return Err(ContractError::CancelClosed {});
+            }
+        }
+        call_off(deps, reason)
+    }
+
+    /// governance calls the campaign off and refunds every contribution at once
+    pub fn force_cancel(
+        mut deps: DepsMut,
+        _env: Env,
+        reason: String,
+    ) -> Result<Response, ContractError> {
+        ensure_unsettled(deps.storage)?;
+        let pledges = pledges(deps.storage)?;
+        let mut res = call_off(deps.branch(), reason.clone())?;
+        for (addr, coins) in pledges {
+            for coin in &coins {
+                CONTRIBUTIONS.remove(deps.storage, (&addr, &coin.denom));
+            }
+            for (to, coins) in reclaim(deps.storage, &addr, coins)? {
+                res = res.add_submessages(payouts(deps.storage, &to, coins, None)?);
+            }
+        }
+        Ok(res.add_event(
+            Event::new("sudo")
+                .add_attribute("action", "force-cancel")
+                .add_attribute("reason", reason),
+        ))
+    }
+
+    /// governance resolves the campaign without waiting for the deadline or a pause to end
+    pub fn force_resolve(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+        ensure_unsettled(deps.storage)?;
+        let res = settle(deps, env)?;
+        Ok(res.add_event(Event::new("sudo").add_attribute("action", "force-resolve")))
+    }
+
+    fn call_off(deps: DepsMut, reason: String) -> Result<Response, ContractError> {
+        OUTCOME.save(
+            deps.storage,
+            &Outcome::Cancelled {
+                reason: reason.clone(),
+            },
+        )?;
+
+        let res = Response::new()
+            .add_attribute("method", "cancel")
+            .add_attribute("reason", reason)
+            .add_submessages(tax_payouts(deps.storage)?);
+        for coin in taxes(deps.storage)? {
+            REFUND_TAXES.remove(deps.storage, &coin.denom);
+        }
+        Ok(res)
+    }
+
+    /// if threshold isn't reached, refund all contributions
+    /// if threshold is reached, send all funds to the receiver
+    /// only valid once the deadline has expired
+    pub fn resolve(
+        deps: DepsMut,
+        env: Env,
+        _: MessageInfo,
+        _: ExecuteMsg,
+    ) -> Result<Response, ContractError> {
+        if !load_deadline(deps.as_ref(), &env)?.is_expired(&env.block) {
+            return Err(ContractError::DeadlineNotReached {});
+        }
+        ensure_unsettled(deps.storage)?;
+        ensure_not_paused(deps.storage, &env)?;
+        settle(deps, env)
+    }
+
+    fn settle(mut deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+        OUTCOME.save(deps.storage, &Outcome::Resolved {})?;
+        // without a fresh price the threshold cannot be judged, the campaign fails rather
+        // than leaving the funds locked until the oracle comes back
+        let priced = refresh_prices(deps.branch(), &env, None).is_ok();
+
+        let threshold = current_threshold(deps.as_ref(), &env)?;
+
+        // only the pledges that can be pulled right now count
+        let (honoured, defaulted) = check_pledges(deps.as_ref(), &env)?;
+        let mut total_contributions = pot(deps.storage)?;
+        if let Some(token) = CW20_TOKEN.may_load(deps.storage)? {
+            let pullable = honoured.iter().map(|(_, amount)| *amount).sum();
+            total_contributions += value(deps.storage, token.as_str(), pullable)?;
+        }
+
+        if !priced || total_contributions < threshold {
+            // refund all contributions, in the denoms they were made in
+            let mut res = Response::new()
+                .add_attribute("method", "resolve")
+                .add_attribute("status", "refund");
+            if !priced {
+                res = res.add_attribute("reason", "stale-prices");
+            }
+
+            for (addr, coins) in pledges(deps.storage)? {
+                for (to, coins) in reclaim(deps.storage, &addr, coins)? {
+                    res = res.add_submessages(payouts(deps.storage, &to, coins, None)?);
+                }
+            }
+
+            Ok(res.add_submessages(tax_payouts(deps.storage)?))
+        } else {
+            // send all funds to the receivers, notifying them if they asked for it
+            let hook = if RECEIVER_HOOK.may_load(deps.storage)?.unwrap_or_default() {
+                let mut contributors: Vec<String> = pledges(deps.storage)?
+                    .into_iter()
+                    .map(|(addr, _)| addr.into_string())
+                    .chain(honoured.iter().map(|(addr, _)| addr.to_string()))
+                    .collect();
+                contributors.sort();
+                contributors.dedup();
+                Some(ThresholdReachedHook {
+                    campaign: env.contract.address.to_string(),
+                    total: Coin {
+                        denom: THRESHOLD_COIN.load(deps.storage)?.denom,
+                        amount: total_contributions,
+                    },
+                    contributors,
+                })
+            } else {
+                None
+            };
+            // stored actions run first and spend from the collected funds,
+            // the receivers get what is left
+            let actions = ACTIONS.may_load(deps.storage)?.unwrap_or_default();
+            let mut left = NativeBalance(pot_coins(deps.storage)?);
+            let mut res = Response::new()
+                .add_attribute("method", "resolve")
+                .add_attribute("status", "send")
+                .add_attribute("actions", actions.len().to_string());
+            // a failing action is recorded by `reply` without reverting the resolution,
+            // the action's index following from its reply id
+            if !actions.is_empty() {
+                let first = REPLY_COUNT.may_load(deps.storage)?.unwrap_or_default();
+                ACTION_REPLIES.save(deps.storage, &first)?;
+            }
+            for (index, action) in actions.into_iter().enumerate() {
+                let id = next_reply_id(deps.storage)?;
+                // an action the pot cannot fund fails right away, its funds stay with the receivers
+                match left.clone() - action_funds(std::slice::from_ref(&action)) {
+                    Ok(rest) => {
+                        left = rest;
+                        res = res.add_submessage(SubMsg::reply_on_error(action, id));
+                    }
+                    Err(_) => {
+                        let index = index as u32;
+                        FAILED_ACTIONS.save(
+                            deps.storage,
+                            index,
+                            &"spends more than was collected".to_string(),
+                        )?;
+                        res = res.add_attribute("action-failed", index.to_string());
+                    }
+                }
+            }
+            let coins = left.into_vec();
+            let mut fees = NativeBalance::default();
+            let (fee, coins) = fee_cut(deps.storage, coins)?;
+            if let Some((treasury, cut)) = fee {
+                fees += NativeBalance(cut.clone());
+                res = res.add_submessages(payouts(deps.storage, &treasury, cut, None)?);
+            }
+            for (receiver, coins) in split(deps.storage, &coins)? {
+                // accounts among the receivers cannot take the hook, they get a bank send
+                let hook = hook
+                    .clone()
+                    .filter(|_| is_contract(deps.as_ref(), &receiver));
+                res = res.add_submessages(payouts(deps.storage, &receiver, coins, hook)?);
+            }
+
+            // pull the allowance pledges straight to the receivers, a failing pull is
+            // reported by `reply` without reverting the resolution, as with payouts
+            if let Some(token) = CW20_TOKEN.may_load(deps.storage)? {
+                for (owner, amount) in honoured {
+                    let pledged = Coin {
+                        denom: token.to_string(),
+                        amount,
+                    };
+                    let (fee, rest) = fee_cut(deps.storage, vec![pledged])?;
+                    let mut targets = split(deps.storage, &rest)?;
+                    if let Some((treasury, cut)) = fee {
+                        fees += NativeBalance(cut.clone());
+                        targets.insert(0, (treasury, cut));
+                    }
+                    for (receiver, share) in targets {
+                        if share[0].amount.is_zero() {
+                            continue;
+                        }
+                        let id = next_reply_id(deps.storage)?;
+                        PULLS.save(
+                            deps.storage,
+                            id,
+                            &Pull {
+                                owner: owner.clone(),
+                                recipient: receiver.clone(),
+                                amount: share[0].amount,
+                            },
+                        )?;
+                        res = res.add_submessage(SubMsg::reply_always(
+                            WasmMsg::Execute {
+                                contract_addr: token.to_string(),
+                                msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                                    owner: owner.to_string(),
+                                    recipient: receiver,
+                                    amount: share[0].amount,
+                                })?,
+                                funds: vec![],
+                            },
+                            id,
+                        ));
+                    }
+                }
+            }
+            for (owner, amount) in &defaulted {
+                DEFAULTED_PLEDGES.save(deps.storage, owner, amount)?;
+                res = res.add_attribute("pledge-defaulted", owner.as_str());
+            }
+            if FEE.may_load(deps.storage)?.is_some() {
+                fees.normalize();
+                res = res.add_attribute("fee", coins_to_string(&fees.into_vec()));
+            }
+            Ok(res)
+        }
+    }
+
+    /// send a payout that failed at resolution again, the recipient may redirect it
+    pub fn retry_payout(
+        deps: DepsMut,
+        info: MessageInfo,
+        recipient: String,
+        redirect_to: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let coins = FAILED_PAYOUTS
+            .may_load(deps.storage, &recipient)?
+            .ok_or(ContractError::NoFailedPayout {})?;
+        let to = match redirect_to {
+            Some(to) if info.sender == recipient => deps.api.addr_validate(&to)?.into_string(),
+            Some(_) => return Err(ContractError::Unauthorized {}),
+            None => recipient.clone(),
+        };
+        FAILED_PAYOUTS.remove(deps.storage, &recipient);
+
+        Ok(Response::new()
+            .add_attribute("method", "retry-payout")
+            .add_attribute("recipient", recipient)
+            .add_attribute("to", to.as_str())
+            .add_attribute("amount", coins_to_string(&coins))
+            .add_messages(transfer(deps.storage, &to, coins)?))
+    }
+
+    /// guardian suspends contributions and resolution, at most for `max_pause` and
+    /// `max_pauses` times
+    pub fn pause(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        reason: String,
+    ) -> Result<Response, ContractError> {
+        let guardian = ensure_guardian(deps.storage, &info.sender)?;
+        ensure_unsettled(deps.storage)?;
+        ensure_not_paused(deps.storage, &env)?;
+        let id = PAUSE_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+        if id > guardian.max_pauses {
+            return Err(ContractError::PauseLimit {});
+        }
+        let until = guardian.max_pause.after(&env.block);
+        PAUSE_COUNT.save(deps.storage, &id)?;
+        PAUSES.save(
+            deps.storage,
+            id,
+            &Pause {
+                reason: reason.clone(),
+                paused_at: env.block.time,
+                until,
+                lifted_at: None,
+            },
+        )?;
+        Ok(Response::new()
+            .add_attribute("method", "pause")
+            .add_attribute("reason", reason)
+            .add_attribute("until", until.to_string()))
+    }
+
+    pub fn unpause(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+        ensure_guardian(deps.storage, &info.sender)?;
+        let (id, mut pause) =
+            current_pause(deps.storage, &env)?.ok_or(ContractError::NotPaused {})?;
+        pause.lifted_at = Some(env.block.time);
+        PAUSES.save(deps.storage, id, &pause)?;
+        Ok(Response::new().add_attribute("method", "unpause"))
+    }
+
+    /// owner replaces the allowlist, proofs verified against the former root no longer count
+    pub fn update_allowlist_root(
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+        root: Option<Binary>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &info.sender)?;
+        let res = Response::new().add_attribute("method", "update-allowlist-root");
+        match root {
+            Some(root) => {
+                validate_root(&root)?;
+                ALLOWLIST_ROOT.save(deps.storage, &root)?;
+                Ok(res.add_attribute("root", root.to_base64()))
+            }
+            None => {
+                ALLOWLIST_ROOT.remove(deps.storage);
+                Ok(res.add_attribute("root", "none"))
+            }
+        }
+    }
+
+    /// owner revokes a credential, its holder can no longer pledge more with it
+    pub fn revoke_credential(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        nonce: u64,
+        signature: Option<Binary>,
+    ) -> Result<Response, ContractError> {
+        match signature {
+            // the attester can withdraw what it issued without going through the owner
+            Some(signature) => {
+                let attester = ATTESTER
+                    .may_load(deps.storage)?
+                    .ok_or(ContractError::Unauthorized {})?;
+                let digest = credential::revocation_digest(env.contract.address.as_str(), nonce)?;
+                if !deps
+                    .api
+                    .secp256k1_verify(&digest, &signature, &attester)
+                    .unwrap_or(false)
+                {
+                    return Err(ContractError::Unauthorized {});
+                }
+            }
+            None => ensure_owner(deps.storage, &info.sender)?,
+        }
+        REVOKED_NONCES.save(deps.storage, nonce, &env.block.time)?;
+        Ok(Response::new()
+            .add_attribute("method", "revoke-credential")
+            .add_attribute("nonce", nonce.to_string()))
+    }
+
+    /// owner names new receivers, who all have to accept before being paid
+    pub fn propose_receivers(
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+        receivers: Vec<ReceiverShare>,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &info.sender)?;
+        ensure_unsettled(deps.storage)?;
+        validate_receivers(deps.as_ref(), &receivers)?;
+        RECEIVER_PROPOSAL.save(
+            deps.storage,
+            &ReceiverProposal {
+                receivers,
+                accepted: vec![],
+            },
+        )?;
+        Ok(Response::new().add_attribute("method", "propose-receivers"))
+    }
+
+    /// proposed receiver takes the role, the proposal applies once all of them did
+    pub fn accept_receiver_role(
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        ensure_unsettled(deps.storage)?;
+        let mut proposal = RECEIVER_PROPOSAL
+            .may_load(deps.storage)?
+            .ok_or(ContractError::Unauthorized {})?;
+        let sender = info.sender.as_str();
+        if !proposal
+            .receivers
+            .iter()
+            .any(|receiver| receiver.address == sender)
+            || proposal.accepted.iter().any(|accepted| accepted == sender)
+        {
+            return Err(ContractError::Unauthorized {});
+        }
+        proposal.accepted.push(info.sender.to_string());
+
+        let res = Response::new()
+            .add_attribute("method", "accept-receiver-role")
+            .add_attribute("receiver", info.sender);
+        if proposal.accepted.len() < proposal.receivers.len() {
+            RECEIVER_PROPOSAL.save(deps.storage, &proposal)?;
+            return Ok(res);
+        }
+        RECEIVER_PROPOSAL.remove(deps.storage);
+        RECEIVERS.save(deps.storage, &proposal.receivers)?;
+        Ok(res.add_attribute("receivers-replaced", "true"))
+    }
+
+    /// owner proposes a successor, who has to accept before taking over
+    pub fn propose_owner(
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+        new_owner: String,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &info.sender)?;
+        let new_owner = deps.api.addr_validate(&new_owner)?;
+        PENDING_OWNER.save(deps.storage, &new_owner)?;
+        Ok(Response::new()
+            .add_attribute("method", "propose-owner")
+            .add_attribute("pending-owner", new_owner))
+    }
+
+    pub fn accept_ownership(
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        if PENDING_OWNER.may_load(deps.storage)? != Some(info.sender.clone()) {
+            return Err(ContractError::Unauthorized {});
+        }
+        PENDING_OWNER.remove(deps.storage);
+        OWNER.save(deps.storage, &info.sender)?;
+        Ok(Response::new()
+            .add_attribute("method", "accept-ownership")
+            .add_attribute("owner", info.sender))
+    }
+
+    /// owner leaves the campaign without anybody able to cancel or extend it
+    pub fn renounce_ownership(
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &info.sender)?;
+        OWNER.remove(deps.storage);
+        PENDING_OWNER.remove(deps.storage);
+        Ok(Response::new().add_attribute("method", "renounce-ownership"))
+    }
+
+    /// creator proposes a later deadline, applied once the veto window closes
+    /// unless enough contributors objected
+    pub fn propose_extension(
+        mut deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        deadline: Expiration,
+    ) -> Result<Response, ContractError> {
+        ensure_owner(deps.storage, &info.sender)?;
+        let config = EXTENSION_CONFIG.may_load(deps.storage)?.ok_or_else(|| {
+            ContractError::InvalidExtension {
+                reason: "extensions are disabled".to_string(),
+            }
+        })?;
+        if open_extension(deps.as_ref(), &env)?.is_some() {
+            return Err(ContractError::InvalidExtension {
+                reason: "an extension is already pending".to_string(),
+            });
+        }
+
+        // settle the previous proposal before replacing it
+        let current = settle_deadline(deps.branch(), &env)?;
+
+        if current.is_expired(&env.block) {
+            return Err(ContractError::DeadlinePassed {});
+        }
+        let proposed = EXTENSION_COUNT.may_load(deps.storage)?.unwrap_or_default();
+        if proposed >= config.max_extensions {
+            return Err(ContractError::InvalidExtension {
+                reason: "no extensions left".to_string(),
+            });
+        }
+        if deadline.partial_cmp(&current) != Some(Ordering::Greater) {
+            return Err(ContractError::InvalidExtension {
+                reason: "new deadline must be later than the current one".to_string(),
+            });
+        }
+        let veto_ends = config.veto_period.after(&env.block);
+        if veto_ends.partial_cmp(&current) != Some(Ordering::Less) {
+            return Err(ContractError::InvalidExtension {
+                reason: "veto window must end before the current deadline".to_string(),
+            });
+        }
+
+        let pledged = total_contributions(deps.storage)?;
+        PENDING_EXTENSION.save(
+            deps.storage,
+            &ExtensionProposal {
+                deadline,
+                veto_ends,
+                pledged,
+                objected: Uint128::zero(),
+            },
+        )?;
+        EXTENSION_COUNT.save(deps.storage, &(proposed + 1))?;
+
+        Ok(Response::new()
+            .add_attribute("method", "propose-extension")
+            .add_attribute("deadline", deadline.to_string())
+            .add_attribute("veto-ends", veto_ends.to_string()))
+    }
+
+    /// contributor objects to the pending extension and is refunded under the refund policy,
+    /// only with what it had pledged when the extension was proposed
+    pub fn object_extension(
+        mut deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        ensure_unsettled(deps.storage)?;
+        let mut proposal =
+            open_extension(deps.as_ref(), &env)?.ok_or(ContractError::NoPendingExtension {})?;
+        let id = EXTENSION_COUNT.load(deps.storage)?;
+        if EXTENSION_VOTES.has(deps.storage, (id, &info.sender)) {
+            return Err(ContractError::AlreadyVoted {});
+        }
+        if LATE_PLEDGERS.has(deps.storage, (id, &info.sender)) {
+            return Err(ContractError::InvalidExtension {
+                reason: "pledged after the proposal".to_string(),
+            });
+        }
+
+        let coins = user_coins(deps.storage, &info.sender)?;
+        if coins.is_empty() {
+            return Err(ContractError::NoContribution {});
+        }
+        // prices may have moved since, the tally never exceeds what it is measured against
+        proposal.objected =
+            (proposal.objected + user_value(deps.storage, &info.sender)?).min(proposal.pledged);
+        let res = take_back(
+            deps.branch(),
+            env,
+            info.sender.clone(),
+            coins,
+            "object-extension",
+        )?;
+        PENDING_EXTENSION.save(deps.storage, &proposal)?;
+        EXTENSION_VOTES.save(deps.storage, (id, &info.sender), &false)?;
+
+        Ok(res)
+    }
+
+    /// contributor accepts the pending extension
+    pub fn accept_extension(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        ensure_unsettled(deps.storage)?;
+        open_extension(deps.as_ref(), &env)?.ok_or(ContractError::NoPendingExtension {})?;
+        let id = EXTENSION_COUNT.load(deps.storage)?;
+        if EXTENSION_VOTES.has(deps.storage, (id, &info.sender)) {
+            return Err(ContractError::AlreadyVoted {});
+        }
+        if user_coins(deps.storage, &info.sender)?.is_empty() {
+            return Err(ContractError::NoContribution {});
+        }
+        EXTENSION_VOTES.save(deps.storage, (id, &info.sender), &true)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "accept-extension")
+            .add_attribute("contributor", info.sender))
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetUserContribution { addr } => {
+            to_binary(&query::usercontribution(deps, env, addr)?)
+        }
+        QueryMsg::GetUserContributionCoins { addr } => {
+            to_binary(&query::usercontributioncoins(deps, env, addr)?)
+        }
+        QueryMsg::GetTotalContribution {} => to_binary(&query::totalcontribution(deps, env)?),
+        QueryMsg::GetDeadline {} => to_binary(&query::deadline(deps, env)?),
+        QueryMsg::GetStatus {} => to_binary(&query::status(deps, env)?),
+        QueryMsg::GetExtension {} => to_binary(&query::extension(deps, env)?),
+        QueryMsg::GetReceivers {} => to_binary(&query::receivers(deps, env)?),
+        QueryMsg::GetActions {} => to_binary(&query::actions(deps, env)?),
+        QueryMsg::GetPledge { addr } => to_binary(&query::pledge(deps, env, addr)?),
+        QueryMsg::GetFailedPayout { addr } => to_binary(&query::failed_payout(deps, env, addr)?),
+        QueryMsg::GetOwnership {} => to_binary(&query::ownership(deps, env)?),
+        QueryMsg::GetAllowlist {} => to_binary(&query::allowlist(deps, env)?),
+        QueryMsg::GetCredential { addr } => to_binary(&query::credential(deps, env, addr)?),
+        QueryMsg::GetIntentNonce { pubkey } => to_binary(&query::intent_nonce(deps, env, pubkey)?),
+        QueryMsg::GetPause {} => to_binary(&query::pause(deps, env)?),
+    }
+}
+
+/// Chain governance settling a stuck campaign.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::ForceCancel { reason } => execute::force_cancel(deps, env, reason),
+        SudoMsg::ForceResolve {} => execute::force_resolve(deps, env),
+    }
+}
+
+/// A resolution submessage failed: a payout is kept for a retry, the share of an allowance
+/// pledge it pulled is recorded as defaulted, an action is recorded as failed.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    // a record is only needed until its submessage ran
+    let payout = PAYOUTS.may_load(deps.storage, msg.id)?;
+    PAYOUTS.remove(deps.storage, msg.id);
+    let pull = PULLS.may_load(deps.storage, msg.id)?;
+    PULLS.remove(deps.storage, msg.id);
+    let err = match msg.result {
+        SubMsgResult::Ok(_) => return Ok(Response::new()),
+        SubMsgResult::Err(err) => err,
+    };
+
+    if let Some(index) = action_index(deps.storage, msg.id)? {
+        let action = &ACTIONS.load(deps.storage)?[index as usize];
+        FAILED_ACTIONS.save(deps.storage, index, &err)?;
+        // the funds the action did not spend are the receivers', to claim with a retry
+        let funds = action_funds(std::slice::from_ref(action));
+        if !funds.is_empty() {
+            for (receiver, coins) in split(deps.storage, &funds)? {
+                owe(deps.storage, &receiver, &coins)?;
+            }
+        }
+        return Ok(Response::new()
+            .add_attribute("method", "reply")
+            .add_attribute("action-failed", index.to_string())
+            .add_attribute("amount", coins_to_string(&funds))
+            .add_attribute("error", err));
+    }
+
+    if let Some(payout) = payout {
+        owe(deps.storage, &payout.recipient, &payout.coins)?;
+
+        return Ok(Response::new()
+            .add_attribute("method", "reply")
+            .add_attribute("payout-failed", payout.recipient)
+            .add_attribute("amount", coins_to_string(&payout.coins))
+            .add_attribute("error", err));
+    }
+
+    // the other shares of the pledge may have gone through, only this one is unpaid
+    let pull = pull.ok_or_else(|| StdError::not_found("threshold_action::state::Pull"))?;
+    DEFAULTED_PLEDGES.update(deps.storage, &pull.owner, |unpaid| -> StdResult<_> {
+        Ok(unpaid.unwrap_or_default() + pull.amount)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("method", "reply")
+        .add_attribute("pledge-defaulted", pull.owner.as_str())
+        .add_attribute("recipient", pull.recipient)
+        .add_attribute("amount", pull.amount.to_string())
+        .add_attribute("error", err))
+}
+
+pub mod query {
+    use super::*;
+
+    // TBD why option and not just the unboxed type?
+
+    #[cw_serde]
+    pub struct ContributionResponse {
+        pub amount: Uint128,
+    }
+
+    pub fn usercontribution(
+        deps: Deps,
+        _env: Env,
+        addr: String,
+    ) -> StdResult<ContributionResponse> {
+        let user = deps.api.addr_validate(&addr)?;
+        // weighted value of all denoms, zero if nothing was pledged
+        Ok(ContributionResponse {
+            amount: user_value(deps.storage, &user)?,
+        })
+    }
+
+    pub fn usercontributioncoins(
+        deps: Deps,
+        _env: Env,
+        addr: String,
+    ) -> StdResult<ContributionCoinsResponse> {
+        let user = deps.api.addr_validate(&addr)?;
+        Ok(ContributionCoinsResponse {
+            coins: user_coins(deps.storage, &user)?,
+        })
+    }
+
+    pub fn totalcontribution(deps: Deps, _env: Env) -> StdResult<ContributionResponse> {
+        Ok(ContributionResponse {
+            amount: pot(deps.storage)?,
+        })
+    }
+
+    pub fn deadline(deps: Deps, env: Env) -> StdResult<DeadlineResponse> {
+        Ok(DeadlineResponse {
+            deadline: load_deadline(deps, &env)?,
+            opening: OPENING.may_load(deps.storage)?,
+        })
+    }
+
+    pub fn status(deps: Deps, env: Env) -> StdResult<StatusResponse> {
+        let threshold_coin = THRESHOLD_COIN.load(deps.storage)?;
+        let threshold = current_threshold(deps, &env)?;
+        let total = totalcontribution(deps, env)?.amount;
+        let fee = FEE.may_load(deps.storage)?;
+        let fee_amount = fee
+            .as_ref()
+            .map(|fee| total.multiply_ratio(fee.fee_bps, TOTAL_BPS))
+            .unwrap_or_default();
+        Ok(StatusResponse {
+            total: Coin {
+                denom: threshold_coin.denom.clone(),
+                amount: total,
+            },
+            pledged: Coin {
+                denom: threshold_coin.denom.clone(),
+                amount: allowance_total(deps.storage)?,
+            },
+            threshold: Coin {
+                denom: threshold_coin.denom.clone(),
+                amount: threshold,
+            },
+            threshold_reached: total >= threshold,
+            pending: RECEIVERS.may_load(deps.storage)?.is_none(),
+            outcome: OUTCOME.may_load(deps.storage)?,
+            fee,
+            fee_amount: Coin {
+                denom: threshold_coin.denom,
+                amount: fee_amount,
+            },
+        })
+    }
+
+    pub fn pledge(deps: Deps, _env: Env, addr: String) -> StdResult<PledgeResponse> {
+        let user = deps.api.addr_validate(&addr)?;
+        Ok(PledgeResponse {
+            amount: ALLOWANCE_PLEDGES
+                .may_load(deps.storage, &user)?
+                .unwrap_or_default(),
+            defaulted: DEFAULTED_PLEDGES
+                .may_load(deps.storage, &user)?
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn failed_payout(deps: Deps, _env: Env, addr: String) -> StdResult<FailedPayoutResponse> {
+        Ok(FailedPayoutResponse {
+            coins: FAILED_PAYOUTS
+                .may_load(deps.storage, &addr)?
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn pause(deps: Deps, env: Env) -> StdResult<PauseResponse> {
+        Ok(PauseResponse {
+            guardian: GUARDIAN.may_load(deps.storage)?,
+            paused: current_pause(deps.storage, &env)?.is_some(),
+            history: PAUSES
+                .range(deps.storage, None, None, Order::Ascending)
+                .map(|item| item.map(|(_, pause)| pause))
+                .collect::<StdResult<_>>()?,
+        })
+    }
+
+    pub fn allowlist(deps: Deps, _env: Env) -> StdResult<AllowlistResponse> {
+        Ok(AllowlistResponse {
+            root: ALLOWLIST_ROOT.may_load(deps.storage)?,
+        })
+    }
+
+    pub fn credential(deps: Deps, _env: Env, addr: String) -> StdResult<CredentialResponse> {
+        let user = deps.api.addr_validate(&addr)?;
+        let credential = CREDENTIALS.may_load(deps.storage, &user)?;
+        let revoked = credential
+            .as_ref()
+            .is_some_and(|credential| REVOKED_NONCES.has(deps.storage, credential.nonce));
+        Ok(CredentialResponse {
+            credential,
+            revoked,
+        })
+    }
+
+    pub fn intent_nonce(deps: Deps, _env: Env, pubkey: Binary) -> StdResult<IntentNonceResponse> {
+        Ok(IntentNonceResponse {
+            nonce: INTENT_NONCES.may_load(deps.storage, &pubkey)?,
+        })
+    }
+
+    pub fn ownership(deps: Deps, _env: Env) -> StdResult<OwnershipResponse> {
+        Ok(OwnershipResponse {
+            owner: OWNER.may_load(deps.storage)?,
+            pending_owner: PENDING_OWNER.may_load(deps.storage)?,
+        })
+    }
+
+    pub fn receivers(deps: Deps, _env: Env) -> StdResult<ReceiversResponse> {
+        Ok(ReceiversResponse {
+            receivers: RECEIVERS.may_load(deps.storage)?.unwrap_or_default(),
+            proposal: RECEIVER_PROPOSAL.may_load(deps.storage)?,
+        })
+    }
+
+    pub fn actions(deps: Deps, _env: Env) -> StdResult<ActionsResponse> {
+        Ok(ActionsResponse {
+            actions: ACTIONS.may_load(deps.storage)?.unwrap_or_default(),
+            failed: FAILED_ACTIONS
+                .range(deps.storage, None, None, Order::Ascending)
+                .map(|item| item.map(|(index, error)| FailedAction { index, error }))
+                .collect::<StdResult<_>>()?,
+        })
+    }
+
+    pub fn extension(deps: Deps, env: Env) -> StdResult<ExtensionResponse> {
+        Ok(ExtensionResponse {
+            config: EXTENSION_CONFIG.may_load(deps.storage)?,
+            proposed: EXTENSION_COUNT.may_load(deps.storage)?.unwrap_or_default(),
+            pending: open_extension(deps, &env)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::query::ContributionResponse;
+    use crate::factory::FactoryConfigResponse;
+    use crate::hook::ReceiverExecuteMsg;
+    use crate::intent::PledgeIntent;
+    use crate::merkle::MerkleTree;
+    use crate::msg::{BeneficiaryContribution, ReceiveMsg};
+    use crate::state::{
+        AntiSnipeConfig, DenomWeight, ExtensionConfig, FeeConfig, GuardianConfig, OracleConfig,
+        RefundPolicy, ThresholdStep,
+    };
+    use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{
+        testing::{mock_dependencies, mock_env, mock_info},
+        Addr, Api, Attribute, CanonicalAddr, ContractResult, Empty, OwnedDeps, RecoverPubkeyError,
+        StakingMsg, SubMsgResponse, SystemError, SystemResult, Timestamp, VerificationError,
+        WasmQuery,
+    };
+    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
+    use cw_utils::Scheduled;
+    use k256::ecdsa::{signature::hazmat::PrehashSigner, Signature, SigningKey};
+    use k256::elliptic_curve::sec1::ToEncodedPoint;
+    use std::marker::PhantomData;
+
+    fn contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(execute, instantiate, query).with_reply(reply);
+        Box::new(contract)
+    }
+
+    /// Campaign for 10 OSMO with every optional feature left out.
+    fn campaign(deadline: Expiration) -> InstantiateMsg {
+        InstantiateMsg {
+            coin_threshold: Coin {
+                denom: "OSMO".to_string(),
+                amount: Uint128::from(10_000_000u128),
+            },
+            deadline,
+            opening: None,
+            receiver: None,
+            receivers: None,
+            threshold_schedule: None,
+            extensions: None,
+            anti_snipe: None,
+            refund_policy: None,
+            min_contribution: None,
+            accepted_denoms: None,
+            oracle: None,
+            cw20: None,
+            receiver_hook: None,
+            actions: None,
+            fee: None,
+            cancel_window: None,
+            require_receiver_acceptance: None,
+            guardian: None,
+            allowlist_root: None,
+            attester: None,
+        }
+    }
+
+    fn contract_builder(deadline: Expiration) -> (App, Addr) {
+        let mut app = App::default();
+        let contract_id = app.store_code(contract());
+        let contract_addr = app
+            .instantiate_contract(
+                contract_id,
+                Addr::unchecked("sender"),
+                &campaign(deadline),
+                &[],
+                "Threshold Funding",
+                None,
+            )
+            .unwrap();
+        (app, contract_addr)
+    }
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies();
+
+        let msg_no_receiver = campaign(Expiration::AtTime(Timestamp::from_seconds(10)));
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg_no_receiver).unwrap();
+        assert_eq!(0, res.messages.len());
+        assert_eq!(5, res.attributes.len());
 
         let init_threshold_coin = THRESHOLD_COIN.load(&deps.storage).unwrap();
         assert_eq!("OSMO", init_threshold_coin.denom);
         assert_eq!(Uint128::from(10_000_000u128), init_threshold_coin.amount);
 
-        let init_deadline = DEADLINE.load(&deps.storage).unwrap();
-        assert_eq!(Timestamp::from_seconds(10), init_deadline);
+        let init_deadline = DEADLINE.load(&deps.storage).unwrap();
+        assert_eq!(
+            Expiration::AtTime(Timestamp::from_seconds(10)),
+            init_deadline
+        );
+
+        let init_receiver_none = RECEIVERS.load(&deps.storage).unwrap();
+        assert_eq!("creator", init_receiver_none[0].address);
+
+        // it worked, let's query the state
+        // let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+        // let value: GetCountResponse = from_binary(&res).unwrap();
+        // assert_eq!(17, value.count);
+    }
+
+    // #[test]
+    // fn query_usercontribution() {
+    //     let mut deps = mock_dependencies();
+
+    //     instantiate(
+    //         deps.as_mut(),
+    //         mock_env(),
+    //         mock_info("creator", &[]),
+    //         InstantiateMsg {
+    //             coin_threshold: Coin {
+    //                 denom: "OSMO".to_string(),
+    //                 amount: Uint128::from(10_000_000u128),
+    //             },
+    //             deadline: Timestamp::from_seconds(10),
+    //             receiver: None,
+    //         },
+    //     )
+    //     .unwrap();
+
+    //     execute(
+    //         deps.as_mut(),
+    //         mock_env(),
+    //         mock_info("anyone", &[]),
+    //         ExecuteMsg::ContributionMsg {
+    //             coin: Coin {
+    //                 denom: "OSMO".to_string(),
+    //                 amount: Uint128::from(1_000_000u128),
+    //             },
+    //         },
+    //     )
+    //     .unwrap();
+
+    //     let _res = query(
+    //         deps.as_ref(),
+    //         mock_env(),
+    //         QueryMsg::GetUserContribution {
+    //             addr: "anyone".to_string(),
+    //         },
+    //     )
+    //     .unwrap();
+    //     // let err = from_binary(&res).unwrap_err();
+    //     // assert that value is of Error DeadlinePassed
+    //     // assert_eq!(ContractError::DeadlinePassed {}, err.downcast().unwrap());
+    //     // assert_eq!(Uint128::from(1_000_000u128), value.amount.unwrap());
+    // }
+
+    // #[test]
+    // fn onecontribution() {
+    //     let (mut app, contract_addr) = contract_builder(Timestamp::from_seconds(10));
+
+    //     let c = Coin {
+    //         denom: String::from("OSMO"),
+    //         amount: Uint128::from(1000u128),
+    //     };
+
+    //     let r1_addr = Addr::unchecked("sender1");
+    //     let r1 = app.execute_contract(
+    //         r1_addr.clone(),
+    //         contract_addr.clone(),
+    //         &ExecuteMsg::ContributionMsg { coin: c.clone() },
+    //         &[c.clone(), c.clone(), c.clone()],
+    //     );
+
+    //     dbg!(&r1);
+    //     // assert!(r1.is_ok());
+
+    //     let query_resp: ContributionResponse = app
+    //         .wrap()
+    //         .query_wasm_smart(
+    //             contract_addr,
+    //             &QueryMsg::GetUserContribution {
+    //                 addr: r1_addr.into_string(),
+    //             },
+    //         )
+    //         .unwrap();
+
+    //     assert_eq!(
+    //         query_resp,
+    //         ContributionResponse {
+    //             amount: Uint128::from(1000u128),
+    //         }
+    //     );
+    // }
+
+    #[test]
+    fn totalcontribution_0() {
+        let mut app = App::default();
+        let contract_id = app.store_code(contract());
+        let contract_addr = app
+            .instantiate_contract(
+                contract_id,
+                Addr::unchecked("sender"),
+                &campaign(Expiration::AtTime(Timestamp::from_seconds(10))),
+                &[],
+                "Threshold Funding",
+                None,
+            )
+            .unwrap();
+
+        let resp: ContributionResponse = app
+            .wrap()
+            .query_wasm_smart(contract_addr, &QueryMsg::GetTotalContribution {})
+            .unwrap();
+
+        assert_eq!(
+            resp,
+            ContributionResponse {
+                amount: Uint128::from(0u128),
+            }
+        );
+    }
+
+    // #[test]
+    // fn totalcontribution_100() {
+    //     let mut app = App::default();
+    //     let contract_id = app.store_code(contract());
+    //     let contract_addr = app
+    //         .instantiate_contract(
+    //             contract_id,
+    //             Addr::unchecked("sender"),
+    //             &InstantiateMsg {
+    //                 coin_threshold: Coin {
+    //                     denom: "OSMO".to_string(),
+    //                     amount: Uint128::from(10_000_000u128),
+    //                 },
+    //                 deadline: Timestamp::from_seconds(10),
+    //                 receiver: None,
+    //             },
+    //             &[],
+    //             "Threshold Funding",
+    //             None,
+    //         )
+    //         .unwrap();
+
+    //     let c = Coin {
+    //         denom: "OSMO".to_string(),
+    //         amount: Uint128::from(50u128),
+    //     };
+    //     let _r1 = app.execute_contract(
+    //         Addr::unchecked("sender1"),
+    //         contract_addr.clone(),
+    //         &ExecuteMsg::ContributionMsg { coin: c.clone() },
+    //         &[c.clone()],
+    //     );
+
+    //     let _r2 = app.execute_contract(
+    //         Addr::unchecked("sender2"),
+    //         contract_addr.clone(),
+    //         &ExecuteMsg::ContributionMsg { coin: c.clone() },
+    //         &[c.clone()],
+    //     );
+
+    //     let resp: ContributionResponse = app
+    //         .wrap()
+    //         .query_wasm_smart(contract_addr, &QueryMsg::GetTotalContribution {})
+    //         .unwrap();
+    //     assert_eq!(
+    //         resp,
+    //         ContributionResponse {
+    //             amount: Uint128::from(100u128),
+    //         }
+    //     );
+    // }
+
+    #[test]
+    fn contribution_and_status() {
+        let (mut app, contract_addr) =
+            contract_builder(Expiration::AtTime(mock_env().block.time.plus_seconds(100)));
+        app.init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(
+                    storage,
+                    &Addr::unchecked("sender1"),
+                    vec![Coin::new(10_000_000, "OSMO")],
+                )
+                .unwrap()
+        });
+
+        let c = Coin::new(4_000_000, "OSMO");
+        app.execute_contract(
+            Addr::unchecked("sender1"),
+            contract_addr.clone(),
+            &ExecuteMsg::ContributionMsg {
+                coin: c.clone(),
+                allowlist: None,
+                credential: None,
+                beneficiary: None,
+            },
+            &[c],
+        )
+        .unwrap();
+
+        let resp: StatusResponse = app
+            .wrap()
+            .query_wasm_smart(contract_addr, &QueryMsg::GetStatus {})
+            .unwrap();
+        assert_eq!(
+            resp,
+            StatusResponse {
+                total: Coin::new(4_000_000, "OSMO"),
+                pledged: Coin::new(0, "OSMO"),
+                threshold: Coin::new(10_000_000, "OSMO"),
+                threshold_reached: false,
+                pending: false,
+                outcome: None,
+                fee: None,
+                fee_amount: Coin::new(0, "OSMO"),
+            }
+        );
+    }
+
+    #[test]
+    fn linear_threshold_schedule() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                threshold_schedule: Some(ThresholdSchedule::Linear {
+                    floor: Uint128::new(4_000_000),
+                }),
+                ..campaign(Expiration::AtTime(env.block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+
+        let mut later = env.clone();
+        later.block.time = env.block.time.plus_seconds(50);
+        let status = query::status(deps.as_ref(), later).unwrap();
+        assert_eq!(Uint128::new(7_000_000), status.threshold.amount);
+
+        later = env.clone();
+        later.block.time = env.block.time.plus_seconds(200);
+        let status = query::status(deps.as_ref(), later).unwrap();
+        assert_eq!(Uint128::new(4_000_000), status.threshold.amount);
+    }
+
+    #[test]
+    fn stepwise_threshold_schedule() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let steps = |first: u128, second: u128| ThresholdSchedule::Stepwise {
+            steps: vec![
+                ThresholdStep {
+                    at: env.block.time.plus_seconds(10),
+                    amount: Uint128::new(first),
+                },
+                ThresholdStep {
+                    at: env.block.time.plus_seconds(20),
+                    amount: Uint128::new(second),
+                },
+            ],
+        };
+        let msg = |schedule| InstantiateMsg {
+            threshold_schedule: Some(schedule),
+            ..campaign(Expiration::AtTime(env.block.time.plus_seconds(100)))
+        };
+
+        // steps may not raise the threshold again
+        let err = instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            msg(steps(5_000_000, 8_000_000)),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InvalidThresholdSchedule { .. }
+        ));
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            msg(steps(8_000_000, 5_000_000)),
+        )
+        .unwrap();
+
+        let mut later = env.clone();
+        later.block.time = env.block.time.plus_seconds(15);
+        let status = query::status(deps.as_ref(), later).unwrap();
+        assert_eq!(Uint128::new(8_000_000), status.threshold.amount);
+    }
+
+    fn env_at(seconds: u64, blocks: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env.block.height += blocks;
+        env
+    }
+
+    fn contribute(
+        deps: DepsMut,
+        env: Env,
+        sender: &str,
+        amount: u128,
+    ) -> Result<Response, ContractError> {
+        let coin = Coin::new(amount, "OSMO");
+        execute(
+            deps,
+            env,
+            mock_info(sender, std::slice::from_ref(&coin)),
+            ExecuteMsg::ContributionMsg {
+                coin,
+                allowlist: None,
+                credential: None,
+                beneficiary: None,
+            },
+        )
+    }
+
+    #[test]
+    fn deadline_boundaries() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                opening: Some(Scheduled::AtTime(mock_env().block.time.plus_seconds(10))),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+
+        // contributions open at the opening time, inclusive
+        let err = contribute(deps.as_mut(), env_at(9, 0), "user", 1000).unwrap_err();
+        assert!(matches!(err, ContractError::NotOpen {}));
+        contribute(deps.as_mut(), env_at(10, 0), "user", 1000).unwrap();
+
+        // the last block before the deadline still accepts contributions and refunds
+        execute(
+            deps.as_mut(),
+            env_at(99, 0),
+            mock_info("user", &[]),
+            ExecuteMsg::RefundMsg {},
+        )
+        .unwrap();
+        contribute(deps.as_mut(), env_at(99, 0), "user", 1000).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env_at(99, 0),
+            mock_info("anyone", &[]),
+            ExecuteMsg::ResolveMsg {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::DeadlineNotReached {}));
+
+        // from the deadline on, only resolution is possible
+        let err = contribute(deps.as_mut(), env_at(100, 0), "user", 1000).unwrap_err();
+        assert!(matches!(err, ContractError::DeadlinePassed {}));
+        let err = execute(
+            deps.as_mut(),
+            env_at(100, 0),
+            mock_info("user", &[]),
+            ExecuteMsg::RefundMsg {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::DeadlinePassed {}));
+        let res = execute(
+            deps.as_mut(),
+            env_at(100, 0),
+            mock_info("anyone", &[]),
+            ExecuteMsg::ResolveMsg {},
+        )
+        .unwrap();
+        assert_eq!(1, res.messages.len());
+    }
+
+    #[test]
+    fn height_deadline() {
+        let mut deps = mock_dependencies();
+        let msg = |threshold_schedule| InstantiateMsg {
+            threshold_schedule,
+            ..campaign(Expiration::AtHeight(mock_env().block.height + 5))
+        };
+
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            msg(Some(ThresholdSchedule::Linear {
+                floor: Uint128::zero(),
+            })),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InvalidThresholdSchedule { .. }
+        ));
+
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            msg(None),
+        )
+        .unwrap();
+
+        // time does not matter for a height-based deadline
+        contribute(deps.as_mut(), env_at(1_000, 4), "user", 1000).unwrap();
+        let err = contribute(deps.as_mut(), env_at(0, 5), "user", 1000).unwrap_err();
+        assert!(matches!(err, ContractError::DeadlinePassed {}));
+        execute(
+            deps.as_mut(),
+            env_at(0, 5),
+            mock_info("anyone", &[]),
+            ExecuteMsg::ResolveMsg {},
+        )
+        .unwrap();
+    }
+
+    fn extensible_campaign(deps: DepsMut, max_extensions: u32) {
+        instantiate(
+            deps,
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                extensions: Some(ExtensionConfig {
+                    max_extensions,
+                    veto_period: Duration::Time(10),
+                    veto_threshold: Decimal::percent(50),
+                }),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+    }
+
+    fn exec_at(
+        deps: DepsMut,
+        seconds: u64,
+        sender: &str,
+        msg: ExecuteMsg,
+    ) -> Result<Response, ContractError> {
+        execute(deps, env_at(seconds, 0), mock_info(sender, &[]), msg)
+    }
+
+    #[test]
+    fn deadline_extension() {
+        let mut deps = mock_dependencies();
+        extensible_campaign(deps.as_mut(), 1);
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 6000).unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "bob", 4000).unwrap();
+
+        let extended = Expiration::AtTime(mock_env().block.time.plus_seconds(200));
+        let propose = ExecuteMsg::ProposeExtension { deadline: extended };
+        let err = exec_at(deps.as_mut(), 50, "alice", propose.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        exec_at(deps.as_mut(), 50, "creator", propose.clone()).unwrap();
+
+        exec_at(deps.as_mut(), 55, "alice", ExecuteMsg::AcceptExtension {}).unwrap();
+        let err = exec_at(deps.as_mut(), 55, "alice", ExecuteMsg::ObjectExtension {}).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyVoted {}));
+        let res = exec_at(deps.as_mut(), 55, "bob", ExecuteMsg::ObjectExtension {}).unwrap();
+        assert_eq!(1, res.messages.len());
+
+        // the veto window closed with 40% objecting, below the 50% veto threshold
+        let resp = query::deadline(deps.as_ref(), env_at(60, 0)).unwrap();
+        assert_eq!(extended, resp.deadline);
+        contribute(deps.as_mut(), env_at(150, 0), "bob", 1000).unwrap();
+
+        let err = exec_at(deps.as_mut(), 150, "creator", propose).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidExtension { .. }));
+    }
+
+    #[test]
+    fn settled_extension() {
+        let mut deps = mock_dependencies();
+        extensible_campaign(deps.as_mut(), 1);
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 6000).unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "bob", 4000).unwrap();
+        let propose = ExecuteMsg::ProposeExtension {
+            deadline: Expiration::AtTime(mock_env().block.time.plus_seconds(200)),
+        };
+        exec_at(deps.as_mut(), 50, "creator", propose).unwrap();
+
+        // governance settled the campaign while the veto window was open
+        sudo(deps.as_mut(), env_at(52, 0), SudoMsg::ForceResolve {}).unwrap();
+        let err = exec_at(deps.as_mut(), 55, "alice", ExecuteMsg::AcceptExtension {}).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyResolved {}));
+        let err = exec_at(deps.as_mut(), 55, "bob", ExecuteMsg::ObjectExtension {}).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyResolved {}));
+    }
+
+    #[test]
+    fn objections_after_proposal() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                extensions: Some(ExtensionConfig {
+                    max_extensions: 1,
+                    veto_period: Duration::Time(10),
+                    veto_threshold: Decimal::percent(50),
+                }),
+                refund_policy: Some(RefundPolicy::TaxOnThreshold {
+                    tax: Decimal::percent(10),
+                }),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 6_000_000).unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "bob", 4_000_000).unwrap();
+        let propose = ExecuteMsg::ProposeExtension {
+            deadline: Expiration::AtTime(mock_env().block.time.plus_seconds(200)),
+        };
+        exec_at(deps.as_mut(), 50, "creator", propose).unwrap();
+
+        // a pledge made once the proposal is open is not part of its tally
+        contribute(deps.as_mut(), env_at(52, 0), "carol", 20_000_000).unwrap();
+        let err = exec_at(deps.as_mut(), 53, "carol", ExecuteMsg::ObjectExtension {}).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidExtension { .. }));
+        let transfer = ExecuteMsg::TransferPledge {
+            to: "alice".to_string(),
+            amount: Coin::new(1_000_000, "OSMO"),
+            allowlist: None,
+            credential: None,
+        };
+        exec_at(deps.as_mut(), 53, "carol", transfer).unwrap();
+        let err = exec_at(deps.as_mut(), 54, "alice", ExecuteMsg::ObjectExtension {}).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidExtension { .. }));
+
+        // objecting is a refund like any other, taxed once the threshold is reached
+        let res = exec_at(deps.as_mut(), 55, "bob", ExecuteMsg::ObjectExtension {}).unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: vec![Coin::new(3_600_000, "OSMO")],
+            }),
+            res.messages[0].msg
+        );
+        let resp = query::extension(deps.as_ref(), env_at(55, 0)).unwrap();
+        assert_eq!(Uint128::new(4_000_000), resp.pending.unwrap().objected);
+    }
+
+    #[test]
+    fn vetoed_extension() {
+        let mut deps = mock_dependencies();
+        extensible_campaign(deps.as_mut(), 2);
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4000).unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "bob", 6000).unwrap();
+
+        let propose = ExecuteMsg::ProposeExtension {
+            deadline: Expiration::AtTime(mock_env().block.time.plus_seconds(200)),
+        };
+        exec_at(deps.as_mut(), 50, "creator", propose).unwrap();
+        exec_at(deps.as_mut(), 55, "bob", ExecuteMsg::ObjectExtension {}).unwrap();
+
+        let resp = query::extension(deps.as_ref(), env_at(55, 0)).unwrap();
+        assert_eq!(Uint128::new(6000), resp.pending.unwrap().objected);
+        let resp = query::deadline(deps.as_ref(), env_at(60, 0)).unwrap();
+        assert_eq!(
+            Expiration::AtTime(mock_env().block.time.plus_seconds(100)),
+            resp.deadline
+        );
+        exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+    }
+
+    #[test]
+    fn anti_snipe_extension() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                anti_snipe: Some(AntiSnipeConfig {
+                    window: Duration::Time(10),
+                    extension: Duration::Time(30),
+                    max_extension: Duration::Time(45),
+                }),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        let deadline = |deps: Deps| query::deadline(deps, mock_env()).unwrap().deadline;
+        let at = |seconds| Expiration::AtTime(mock_env().block.time.plus_seconds(seconds));
+
+        contribute(deps.as_mut(), env_at(80, 0), "alice", 1000).unwrap();
+        assert_eq!(at(100), deadline(deps.as_ref()));
+
+        let res = contribute(deps.as_mut(), env_at(90, 0), "bob", 1000).unwrap();
+        assert_eq!(at(130), deadline(deps.as_ref()));
+        assert!(res.attributes.iter().any(|a| a.key == "deadline-extended"));
+
+        // a late refund extends too, up to the 45 seconds cap
+        exec_at(deps.as_mut(), 125, "alice", ExecuteMsg::RefundMsg {}).unwrap();
+        assert_eq!(at(145), deadline(deps.as_ref()));
+        contribute(deps.as_mut(), env_at(140, 0), "alice", 1000).unwrap();
+        assert_eq!(at(145), deadline(deps.as_ref()));
+
+        let err = contribute(deps.as_mut(), env_at(145, 0), "alice", 1000).unwrap_err();
+        assert!(matches!(err, ContractError::DeadlinePassed {}));
+    }
+
+    #[test]
+    fn anti_snipe_keeps_linear_schedule() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                threshold_schedule: Some(ThresholdSchedule::Linear {
+                    floor: Uint128::zero(),
+                }),
+                anti_snipe: Some(AntiSnipeConfig {
+                    window: Duration::Time(10),
+                    extension: Duration::Time(30),
+                    max_extension: Duration::Time(30),
+                }),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        let threshold = |deps: Deps, seconds| {
+            query::status(deps, env_at(seconds, 0))
+                .unwrap()
+                .threshold
+                .amount
+        };
+
+        assert_eq!(Uint128::new(500_000), threshold(deps.as_ref(), 95));
+        contribute(deps.as_mut(), env_at(95, 0), "alice", 1).unwrap();
+        assert_eq!(Uint128::new(500_000), threshold(deps.as_ref(), 95));
+        // the floor is still reached at the initial deadline
+        assert_eq!(Uint128::zero(), threshold(deps.as_ref(), 100));
+        exec_at(deps.as_mut(), 130, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+    }
+
+    fn campaign_with_refund_policy(deps: DepsMut, refund_policy: RefundPolicy) {
+        instantiate(
+            deps,
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                refund_policy: Some(refund_policy),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn refund_lock() {
+        let mut deps = mock_dependencies();
+        campaign_with_refund_policy(deps.as_mut(), RefundPolicy::LockOnThreshold {});
+
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4_000_000).unwrap();
+        exec_at(deps.as_mut(), 10, "alice", ExecuteMsg::RefundMsg {}).unwrap();
+
+        contribute(deps.as_mut(), env_at(20, 0), "alice", 4_000_000).unwrap();
+        contribute(deps.as_mut(), env_at(20, 0), "bob", 6_000_000).unwrap();
+        let err = exec_at(deps.as_mut(), 30, "alice", ExecuteMsg::RefundMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::RefundsLocked {}));
+    }
+
+    #[test]
+    fn refund_tax() {
+        let mut deps = mock_dependencies();
+        campaign_with_refund_policy(
+            deps.as_mut(),
+            RefundPolicy::TaxOnThreshold {
+                tax: Decimal::percent(10),
+            },
+        );
+
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4_000_000).unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "bob", 6_000_000).unwrap();
+        let res = exec_at(deps.as_mut(), 10, "alice", ExecuteMsg::RefundMsg {}).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: vec![Coin::new(3_600_000, "OSMO")],
+            })
+        );
+        let status = query::status(deps.as_ref(), env_at(10, 0)).unwrap();
+        assert_eq!(Uint128::new(6_400_000), status.total.amount);
+
+        // the campaign fails: bob is refunded and the tax goes to the receiver
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: vec![Coin::new(400_000, "OSMO")],
+            })
+        );
+    }
+
+    #[test]
+    fn cancelled_refund_tax() {
+        let mut deps = mock_dependencies();
+        campaign_with_refund_policy(
+            deps.as_mut(),
+            RefundPolicy::TaxOnThreshold {
+                tax: Decimal::percent(10),
+            },
+        );
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4_000_000).unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "bob", 6_000_000).unwrap();
+        exec_at(deps.as_mut(), 10, "alice", ExecuteMsg::RefundMsg {}).unwrap();
+
+        // a receiver refusing the tax does not keep the campaign from being called off
+        let cancel = ExecuteMsg::Cancel {
+            reason: "team split".to_string(),
+        };
+        let res = exec_at(deps.as_mut(), 20, "creator", cancel).unwrap();
+        assert_eq!(
+            vec![SubMsg::reply_always(
+                BankMsg::Send {
+                    to_address: "creator".to_string(),
+                    amount: vec![Coin::new(400_000, "OSMO")],
+                },
+                0
+            )],
+            res.messages
+        );
+        reply(
+            deps.as_mut(),
+            env_at(20, 0),
+            Reply {
+                id: 0,
+                result: SubMsgResult::Err("blocked".to_string()),
+            },
+        )
+        .unwrap();
+        let resp = query::failed_payout(deps.as_ref(), mock_env(), "creator".to_string()).unwrap();
+        assert_eq!(vec![Coin::new(400_000, "OSMO")], resp.coins);
+    }
+
+    #[test]
+    fn partial_withdrawal() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                min_contribution: Some(Uint128::new(1_000_000)),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        let withdraw = |amount| ExecuteMsg::Withdraw {
+            amount: Uint128::new(amount),
+            denom: None,
+        };
+
+        let err = contribute(deps.as_mut(), env_at(0, 0), "bob", 500_000).unwrap_err();
+        assert!(matches!(err, ContractError::ContributionTooLow {}));
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4_000_000).unwrap();
+
+        // what is left must still meet the minimum
+        let err = exec_at(deps.as_mut(), 10, "alice", withdraw(3_500_000)).unwrap_err();
+        assert!(matches!(err, ContractError::ContributionTooLow {}));
+        exec_at(deps.as_mut(), 10, "alice", withdraw(3_000_000)).unwrap();
+        let resp = query::usercontribution(deps.as_ref(), mock_env(), "alice".to_string()).unwrap();
+        assert_eq!(Uint128::new(1_000_000), resp.amount);
+
+        let err = exec_at(deps.as_mut(), 10, "alice", withdraw(2_000_000)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidWithdrawal {}));
+        exec_at(deps.as_mut(), 10, "alice", withdraw(1_000_000)).unwrap();
+        assert!(!CONTRIBUTIONS.has(&deps.storage, (&Addr::unchecked("alice"), "OSMO")));
+    }
+
+    #[test]
+    fn weighted_denoms() {
+        let mut deps = mock_dependencies();
+        let weighted = |denom: &str, percent| DenomWeight {
+            denom: denom.to_string(),
+            weight: Decimal::percent(percent),
+        };
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                accepted_denoms: Some(vec![weighted("OSMO", 200)]),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidDenomWeight { .. }));
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                accepted_denoms: Some(vec![weighted("ATOM", 1000), weighted("JUNO", 50)]),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        let pledge = |deps: DepsMut, sender: &str, coin: Coin| {
+            execute(
+                deps,
+                env_at(0, 0),
+                mock_info(sender, std::slice::from_ref(&coin)),
+                ExecuteMsg::ContributionMsg {
+                    coin,
+                    allowlist: None,
+                    credential: None,
+                    beneficiary: None,
+                },
+            )
+        };
+
+        let err = pledge(deps.as_mut(), "alice", Coin::new(1_000, "USDC")).unwrap_err();
+        assert!(matches!(err, ContractError::UnacceptedDenom { .. }));
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 2_000_000).unwrap();
+        pledge(deps.as_mut(), "alice", Coin::new(500_000, "ATOM")).unwrap();
+        pledge(deps.as_mut(), "bob", Coin::new(2_000_000, "JUNO")).unwrap();
+
+        let resp = query::usercontribution(deps.as_ref(), mock_env(), "alice".to_string()).unwrap();
+        assert_eq!(Uint128::new(7_000_000), resp.amount);
+        let status = query::status(deps.as_ref(), env_at(10, 0)).unwrap();
+        assert_eq!(Uint128::new(8_000_000), status.total.amount);
+        assert!(!status.threshold_reached);
+
+        // withdrawing names the denom
+        exec_at(
+            deps.as_mut(),
+            10,
+            "alice",
+            ExecuteMsg::Withdraw {
+                amount: Uint128::new(100_000),
+                denom: Some("ATOM".to_string()),
+            },
+        )
+        .unwrap();
+        let resp =
+            query::usercontributioncoins(deps.as_ref(), mock_env(), "alice".to_string()).unwrap();
+        assert_eq!(
+            vec![Coin::new(400_000, "ATOM"), Coin::new(2_000_000, "OSMO")],
+            resp.coins
+        );
+
+        // the campaign fails: everyone gets back the denoms they sent
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        assert_eq!(
+            res.messages[1].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: vec![Coin::new(400_000, "ATOM"), Coin::new(2_000_000, "OSMO")],
+            })
+        );
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: vec![Coin::new(2_000_000, "JUNO")],
+            })
+        );
+    }
+
+    #[test]
+    fn cw20_contributions() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                coin_threshold: Coin::new(10_000_000, "token"),
+                cw20: Some("token".to_string()),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        let send = |deps: DepsMut, token: &str, sender: &str, amount| {
+            let msg = Cw20ReceiveMsg {
+                sender: sender.to_string(),
+                amount: Uint128::new(amount),
+                msg: to_binary(&ReceiveMsg::Contribute {
+                    allowlist: None,
+                    credential: None,
+                })
+                .unwrap(),
+            };
+            execute(
+                deps,
+                env_at(0, 0),
+                mock_info(token, &[]),
+                ExecuteMsg::Receive(msg),
+            )
+        };
+        let transfer = |recipient: &str, amount| {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "token".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount: Uint128::new(amount),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        };
+
+        let err = send(deps.as_mut(), "fake-token", "alice", 4_000_000).unwrap_err();
+        assert!(matches!(err, ContractError::UnacceptedDenom { .. }));
+        send(deps.as_mut(), "token", "alice", 4_000_000).unwrap();
+        send(deps.as_mut(), "token", "bob", 10_000_000).unwrap();
+        let resp = query::usercontribution(deps.as_ref(), mock_env(), "alice".to_string()).unwrap();
+        assert_eq!(Uint128::new(4_000_000), resp.amount);
+
+        let res = exec_at(deps.as_mut(), 10, "alice", ExecuteMsg::RefundMsg {}).unwrap();
+        assert_eq!(
+            vec![transfer("alice", 4_000_000)],
+            res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>()
+        );
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        assert_eq!(transfer("creator", 10_000_000), res.messages[0].msg);
+    }
+
+    #[test]
+    fn allowance_pledges() {
+        let mut deps = mock_dependencies();
+        // alice can cover her pledge, bob spent most of his balance
+        deps.querier.update_wasm(|query| {
+            let msg = match query {
+                WasmQuery::Smart { msg, .. } => from_binary(msg).unwrap(),
+                _ => unreachable!(),
+            };
+            let resp = match msg {
+                Cw20QueryMsg::Allowance { owner, .. } => to_binary(&AllowanceResponse {
+                    allowance: Uint128::new(if owner == "alice" {
+                        6_000_000
+                    } else {
+                        4_000_000
+                    }),
+                    expires: Expiration::Never {},
+                }),
+                Cw20QueryMsg::Balance { address } => to_binary(&BalanceResponse {
+                    balance: Uint128::new(if address == "alice" {
+                        6_000_000
+                    } else {
+                        1_000_000
+                    }),
+                }),
+                _ => unreachable!(),
+            };
+            SystemResult::Ok(ContractResult::Ok(resp.unwrap()))
+        });
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                coin_threshold: Coin::new(10_000_000, "token"),
+                cw20: Some("token".to_string()),
+                fee: Some(FeeSource::Custom(FeeConfig {
+                    treasury: "treasury".to_string(),
+                    fee_bps: 1_000,
+                })),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        let pledge = |amount| ExecuteMsg::PledgeAllowance {
+            amount: Uint128::new(amount),
+            allowlist: None,
+            credential: None,
+        };
+
+        let err = exec_at(deps.as_mut(), 0, "alice", pledge(7_000_000)).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientAllowance {}));
+        exec_at(deps.as_mut(), 0, "alice", pledge(6_000_000)).unwrap();
+        exec_at(deps.as_mut(), 0, "bob", pledge(4_000_000)).unwrap();
+        let msg = Cw20ReceiveMsg {
+            sender: "carol".to_string(),
+            amount: Uint128::new(4_000_000),
+            msg: to_binary(&ReceiveMsg::Contribute {
+                allowlist: None,
+                credential: None,
+            })
+            .unwrap(),
+        };
+        execute(
+            deps.as_mut(),
+            env_at(0, 0),
+            mock_info("token", &[]),
+            ExecuteMsg::Receive(msg),
+        )
+        .unwrap();
+        let status = query::status(deps.as_ref(), env_at(10, 0)).unwrap();
+        assert_eq!(Uint128::new(10_000_000), status.pledged.amount);
+
+        // bob's pledge cannot be pulled, alice's is enough to succeed
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        assert_eq!(
+            SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: "token".to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                        owner: "alice".to_string(),
+                        recipient: "creator".to_string(),
+                        amount: Uint128::new(5_400_000),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                },
+                3,
+            ),
+            res.messages[3]
+        );
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("pledge-defaulted", "bob")));
+        let err = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyResolved {}));
+
+        // alice revoked her allowance once the fee was pulled, only the rest is unpaid
+        reply(
+            deps.as_mut(),
+            env_at(100, 0),
+            Reply {
+                id: 3,
+                result: SubMsgResult::Err("no allowance".to_string()),
+            },
+        )
+        .unwrap();
+        let resp = query::pledge(deps.as_ref(), mock_env(), "alice".to_string()).unwrap();
+        assert_eq!(Uint128::new(5_400_000), resp.defaulted);
+        let resp = query::pledge(deps.as_ref(), mock_env(), "bob".to_string()).unwrap();
+        assert_eq!(Uint128::new(4_000_000), resp.defaulted);
+    }
+
+    #[test]
+    fn failed_payouts() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100))),
+        )
+        .unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4_000_000).unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "bob", 2_000_000).unwrap();
+
+        // the campaign fails, refunds are sent as submessages
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        assert_eq!(
+            SubMsg::reply_always(
+                BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: vec![Coin::new(4_000_000, "OSMO")],
+                },
+                1,
+            ),
+            res.messages[1]
+        );
+        let err = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyResolved {}));
+
+        // alice's refund is blocked, bob's goes through
+        reply(
+            deps.as_mut(),
+            env_at(100, 0),
+            Reply {
+                id: 1,
+                result: SubMsgResult::Err("blocked address".to_string()),
+            },
+        )
+        .unwrap();
+        let res = reply(
+            deps.as_mut(),
+            env_at(100, 0),
+            Reply {
+                id: 0,
+                result: SubMsgResult::Ok(SubMsgResponse {
+                    events: vec![],
+                    data: None,
+                }),
+            },
+        )
+        .unwrap();
+        assert!(res.attributes.is_empty());
+        let resp = query::failed_payout(deps.as_ref(), mock_env(), "alice".to_string()).unwrap();
+        assert_eq!(vec![Coin::new(4_000_000, "OSMO")], resp.coins);
+        let resp = query::failed_payout(deps.as_ref(), mock_env(), "bob".to_string()).unwrap();
+        assert!(resp.coins.is_empty());
+        // both records are cleared once their submessage ran
+        assert_eq!(
+            0,
+            PAYOUTS
+                .keys(&deps.storage, None, None, Order::Ascending)
+                .count()
+        );
+
+        // only alice can redirect her refund
+        let retry = |redirect_to: Option<&str>| ExecuteMsg::RetryPayout {
+            recipient: "alice".to_string(),
+            redirect_to: redirect_to.map(String::from),
+        };
+        let err = exec_at(deps.as_mut(), 110, "bob", retry(Some("bob"))).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = exec_at(deps.as_mut(), 110, "alice", retry(Some("alice-cold"))).unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "alice-cold".to_string(),
+                amount: vec![Coin::new(4_000_000, "OSMO")],
+            }),
+            res.messages[0].msg
+        );
+        let err = exec_at(deps.as_mut(), 110, "alice", retry(None)).unwrap_err();
+        assert!(matches!(err, ContractError::NoFailedPayout {}));
+    }
+
+    #[test]
+    fn receiver_hook() {
+        let mut deps = mock_dependencies();
+        // dao is a contract, team a plain account
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::ContractInfo { contract_addr } if contract_addr == "dao" => {
+                let info = ContractInfoResponse::new(1, "creator");
+                SystemResult::Ok(ContractResult::Ok(to_binary(&info).unwrap()))
+            }
+            WasmQuery::ContractInfo { contract_addr } => {
+                SystemResult::Err(SystemError::NoSuchContract {
+                    addr: contract_addr.clone(),
+                })
+            }
+            _ => unreachable!(),
+        });
+        let share = |address: &str, share_bps| ReceiverShare {
+            address: address.to_string(),
+            share_bps,
+        };
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                receivers: Some(vec![share("dao", 8_000), share("team", 2_000)]),
+                receiver_hook: Some(true),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "bob", 6_000_000).unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4_000_000).unwrap();
+
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        let hook = ThresholdReachedHook {
+            campaign: mock_env().contract.address.to_string(),
+            total: Coin::new(10_000_000, "OSMO"),
+            contributors: vec!["alice".to_string(), "bob".to_string()],
+        };
+        assert_eq!(
+            vec![
+                SubMsg::reply_always(
+                    WasmMsg::Execute {
+                        contract_addr: "dao".to_string(),
+                        msg: to_binary(&ReceiverExecuteMsg::ThresholdReachedHook(hook)).unwrap(),
+                        funds: vec![Coin::new(8_000_000, "OSMO")],
+                    },
+                    0,
+                ),
+                SubMsg::reply_always(
+                    BankMsg::Send {
+                        to_address: "team".to_string(),
+                        amount: vec![Coin::new(2_000_000, "OSMO")],
+                    },
+                    1,
+                ),
+            ],
+            res.messages
+        );
+    }
+
+    #[test]
+    fn success_actions() {
+        let mut deps = mock_dependencies();
+        let register = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "registry".to_string(),
+            msg: to_binary(&Empty {}).unwrap(),
+            funds: vec![Coin::new(3_000_000, "OSMO")],
+        });
+        let with_actions = |actions| InstantiateMsg {
+            actions: Some(actions),
+            ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+        };
+
+        let bad = vec![CosmosMsg::Bank(BankMsg::Send {
+            to_address: "auditor".to_string(),
+            amount: vec![Coin::new(1_000, "USDC")],
+        })];
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            with_actions(bad),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAction { .. }));
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            with_actions(vec![register.clone()]),
+        )
+        .unwrap();
+        let resp = query::actions(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(vec![register.clone()], resp.actions);
+
+        // the action spends from the pot, the receiver gets the rest
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 10_000_000).unwrap();
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        assert_eq!(SubMsg::reply_on_error(register.clone(), 0), res.messages[0]);
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: vec![Coin::new(7_000_000, "OSMO")],
+            }),
+            res.messages[1].msg
+        );
+
+        // a failed action does not revert the resolution, its funds go to the receiver
+        let res = reply(
+            deps.as_mut(),
+            env_at(100, 0),
+            Reply {
+                id: 0,
+                result: SubMsgResult::Err("registry closed".to_string()),
+            },
+        )
+        .unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("action-failed", "0")));
+        let resp = query::actions(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(
+            vec![FailedAction {
+                index: 0,
+                error: "registry closed".to_string(),
+            }],
+            resp.failed
+        );
+        let resp = query::failed_payout(deps.as_ref(), mock_env(), "creator".to_string()).unwrap();
+        assert_eq!(vec![Coin::new(3_000_000, "OSMO")], resp.coins);
+    }
+
+    #[test]
+    fn underfunded_actions() {
+        let mut deps = mock_dependencies();
+        let with_actions = |actions| InstantiateMsg {
+            actions: Some(actions),
+            ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+        };
+        // funds these messages spend are not accounted for
+        for action in [
+            CosmosMsg::Bank(BankMsg::Burn {
+                amount: vec![Coin::new(1_000, "OSMO")],
+            }),
+            CosmosMsg::Staking(StakingMsg::Delegate {
+                validator: "validator".to_string(),
+                amount: Coin::new(1_000, "OSMO"),
+            }),
+        ] {
+            let err = instantiate(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                with_actions(vec![action]),
+            )
+            .unwrap_err();
+            assert!(matches!(err, ContractError::InvalidAction { .. }));
+        }
+
+        let grant = CosmosMsg::Bank(BankMsg::Send {
+            to_address: "grantee".to_string(),
+            amount: vec![Coin::new(15_000_000, "OSMO")],
+        });
+        let register = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "registry".to_string(),
+            msg: to_binary(&Empty {}).unwrap(),
+            funds: vec![Coin::new(3_000_000, "OSMO")],
+        });
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            with_actions(vec![grant, register.clone()]),
+        )
+        .unwrap();
+
+        // the grant spends more than the pot: it fails without reverting the resolution
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 10_000_000).unwrap();
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("action-failed", "0")));
+        assert_eq!(SubMsg::reply_on_error(register, 1), res.messages[0]);
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: vec![Coin::new(7_000_000, "OSMO")],
+            }),
+            res.messages[1].msg
+        );
+        let resp = query::actions(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(
+            vec![FailedAction {
+                index: 0,
+                error: "spends more than was collected".to_string(),
+            }],
+            resp.failed
+        );
+    }
+
+    #[test]
+    fn actions_leave_cw20_alone() {
+        let mut deps = mock_dependencies();
+        let transfer = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "auditor".to_string(),
+                amount: Uint128::new(1_000),
+            })
+            .unwrap(),
+            funds: vec![],
+        });
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                coin_threshold: Coin::new(10_000_000, "token"),
+                cw20: Some("token".to_string()),
+                actions: Some(vec![transfer]),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidAction { .. }));
+    }
+
+    #[test]
+    fn split_between_receivers() {
+        let mut deps = mock_dependencies();
+        let with_receivers = |shares: &[(&str, u16)]| InstantiateMsg {
+            receivers: Some(
+                shares
+                    .iter()
+                    .map(|(address, share_bps)| ReceiverShare {
+                        address: address.to_string(),
+                        share_bps: *share_bps,
+                    })
+                    .collect(),
+            ),
+            ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+        };
+
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            with_receivers(&[("team", 6000), ("auditor", 3000)]),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidReceivers { .. }));
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            with_receivers(&[("team", 6000), ("auditor", 1000), ("host", 3000)]),
+        )
+        .unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 10_000_001).unwrap();
+
+        // the dust goes to the first receiver
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        let paid: Vec<_> = res
+            .messages
+            .into_iter()
+            .map(|submsg| match submsg.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                    (to_address, amount[0].amount.u128())
+                }
+                msg => panic!("unexpected {:?}", msg),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("team".to_string(), 6_000_001),
+                ("auditor".to_string(), 1_000_000),
+                ("host".to_string(), 3_000_000)
+            ],
+            paid
+        );
+    }
+
+    #[test]
+    fn platform_fee() {
+        let mut deps = mock_dependencies();
+        let fee = |fee_bps| FeeConfig {
+            treasury: "treasury".to_string(),
+            fee_bps,
+        };
+        deps.querier.update_wasm(move |_| {
+            let config = FactoryConfigResponse {
+                fee: Some(FeeConfig {
+                    treasury: "treasury".to_string(),
+                    fee_bps: 250,
+                }),
+            };
+            SystemResult::Ok(ContractResult::Ok(to_binary(&config).unwrap()))
+        });
+        let with_fee = |source| InstantiateMsg {
+            fee: Some(source),
+            ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+        };
+
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            with_fee(FeeSource::Custom(fee(2_000))),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFee { .. }));
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("factory", &[]),
+            with_fee(FeeSource::Factory {}),
+        )
+        .unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 10_000_000).unwrap();
+        let status = query::status(deps.as_ref(), env_at(10, 0)).unwrap();
+        assert_eq!(Some(fee(250)), status.fee);
+        assert_eq!(Uint128::new(250_000), status.fee_amount.amount);
+
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![Coin::new(250_000, "OSMO")],
+            }),
+            res.messages[0].msg
+        );
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "factory".to_string(),
+                amount: vec![Coin::new(9_750_000, "OSMO")],
+            }),
+            res.messages[1].msg
+        );
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("fee", "250000OSMO")));
+    }
+
+    #[test]
+    fn cancellation() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                cancel_window: Some(CancelWindow::BeforeSuccess {}),
+                refund_policy: Some(RefundPolicy::LockOnThreshold {}),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        let cancel = || ExecuteMsg::Cancel {
+            reason: "team split".to_string(),
+        };
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4_000_000).unwrap();
+
+        let err = exec_at(deps.as_mut(), 10, "alice", cancel()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = exec_at(deps.as_mut(), 10, "alice", ExecuteMsg::ClaimRefund {}).unwrap_err();
+        assert!(matches!(err, ContractError::NotCancelled {}));
+        let res = exec_at(deps.as_mut(), 10, "creator", cancel()).unwrap();
+        assert_eq!(res.attributes[1], Attribute::new("reason", "team split"));
+        let status = query::status(deps.as_ref(), env_at(10, 0)).unwrap();
+        assert_eq!(
+            Some(Outcome::Cancelled {
+                reason: "team split".to_string()
+            }),
+            status.outcome
+        );
+
+        // nothing moves anymore but the claims
+        let err = contribute(deps.as_mut(), env_at(20, 0), "bob", 1_000_000).unwrap_err();
+        assert!(matches!(err, ContractError::Cancelled {}));
+        let err = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::Cancelled {}));
+        let res = exec_at(deps.as_mut(), 200, "alice", ExecuteMsg::ClaimRefund {}).unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: vec![Coin::new(4_000_000, "OSMO")],
+            }),
+            res.messages[0].msg
+        );
+    }
+
+    #[test]
+    fn cancel_window() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                cancel_window: Some(CancelWindow::BeforeSuccess {}),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 10_000_000).unwrap();
+
+        let cancel = ExecuteMsg::Cancel {
+            reason: "changed my mind".to_string(),
+        };
+        let err = exec_at(deps.as_mut(), 10, "creator", cancel).unwrap_err();
+        assert!(matches!(err, ContractError::CancelClosed {}));
+    }
+
+    #[test]
+    fn ownership_transfer() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100))),
+        )
+        .unwrap();
+        let propose = |new_owner: &str| ExecuteMsg::ProposeOwner {
+            new_owner: new_owner.to_string(),
+        };
+        let cancel = || ExecuteMsg::Cancel {
+            reason: "rotating keys".to_string(),
+        };
+
+        let err = exec_at(deps.as_mut(), 10, "mallory", propose("mallory")).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        exec_at(deps.as_mut(), 10, "creator", propose("operator")).unwrap();
+        let err =
+            exec_at(deps.as_mut(), 10, "mallory", ExecuteMsg::AcceptOwnership {}).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        // the creator stays in charge until the transfer is accepted
+        let ownership = query::ownership(deps.as_ref(), env_at(10, 0)).unwrap();
+        assert_eq!(Some(Addr::unchecked("creator")), ownership.owner);
+        assert_eq!(Some(Addr::unchecked("operator")), ownership.pending_owner);
+
+        exec_at(
+            deps.as_mut(),
+            20,
+            "operator",
+            ExecuteMsg::AcceptOwnership {},
+        )
+        .unwrap();
+        let err = exec_at(deps.as_mut(), 20, "creator", cancel()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = exec_at(
+            deps.as_mut(),
+            20,
+            "operator",
+            ExecuteMsg::AcceptOwnership {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        exec_at(
+            deps.as_mut(),
+            30,
+            "operator",
+            ExecuteMsg::RenounceOwnership {},
+        )
+        .unwrap();
+        let ownership = query::ownership(deps.as_ref(), env_at(30, 0)).unwrap();
+        assert_eq!(None, ownership.owner);
+        let err = exec_at(deps.as_mut(), 30, "operator", cancel()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+
+    #[test]
+    fn receiver_acceptance() {
+        let mut deps = mock_dependencies();
+        let share = |address: &str, share_bps| ReceiverShare {
+            address: address.to_string(),
+            share_bps,
+        };
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                receivers: Some(vec![share("bob", 5_000), share("carol", 5_000)]),
+                require_receiver_acceptance: Some(true),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+
+        let err = contribute(deps.as_mut(), env_at(0, 0), "alice", 1_000_000).unwrap_err();
+        assert!(matches!(err, ContractError::Pending {}));
+        assert!(query::status(deps.as_ref(), env_at(0, 0)).unwrap().pending);
+        let err = exec_at(
+            deps.as_mut(),
+            5,
+            "mallory",
+            ExecuteMsg::AcceptReceiverRole {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        exec_at(deps.as_mut(), 5, "bob", ExecuteMsg::AcceptReceiverRole {}).unwrap();
+        let err = exec_at(deps.as_mut(), 5, "bob", ExecuteMsg::AcceptReceiverRole {}).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = contribute(deps.as_mut(), env_at(5, 0), "alice", 1_000_000).unwrap_err();
+        assert!(matches!(err, ContractError::Pending {}));
+        exec_at(deps.as_mut(), 5, "carol", ExecuteMsg::AcceptReceiverRole {}).unwrap();
+        contribute(deps.as_mut(), env_at(10, 0), "alice", 1_000_000).unwrap();
+
+        // a change keeps the current receivers until the new ones accept
+        let propose = ExecuteMsg::ProposeReceivers {
+            receivers: vec![share("dave", 10_000)],
+        };
+        let err = exec_at(deps.as_mut(), 20, "bob", propose.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        exec_at(deps.as_mut(), 20, "creator", propose).unwrap();
+        let receivers = query::receivers(deps.as_ref(), env_at(20, 0)).unwrap();
+        assert_eq!(
+            vec![share("bob", 5_000), share("carol", 5_000)],
+            receivers.receivers
+        );
+        let res = exec_at(deps.as_mut(), 30, "dave", ExecuteMsg::AcceptReceiverRole {}).unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("receivers-replaced", "true")));
+        let receivers = query::receivers(deps.as_ref(), env_at(30, 0)).unwrap();
+        assert_eq!(vec![share("dave", 10_000)], receivers.receivers);
+        assert_eq!(None, receivers.proposal);
+    }
+
+    #[test]
+    fn pending_campaign_resolves() {
+        let pending = || {
+            let mut deps = mock_dependencies();
+            instantiate(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                InstantiateMsg {
+                    receivers: Some(vec![ReceiverShare {
+                        address: "bob".to_string(),
+                        share_bps: 10_000,
+                    }]),
+                    require_receiver_acceptance: Some(true),
+                    ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+                },
+            )
+            .unwrap();
+            deps
+        };
+
+        // the receivers never accepted, there is nothing to refund
+        let mut deps = pending();
+        let res = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+        assert!(res.messages.is_empty());
+        assert!(res.attributes.contains(&Attribute::new("status", "refund")));
+        let mut deps = pending();
+        let res = sudo(deps.as_mut(), env_at(10, 0), SudoMsg::ForceResolve {}).unwrap();
+        assert!(res.messages.is_empty());
+    }
+
+    #[test]
+    fn guardian_pause() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                guardian: Some(GuardianConfig {
+                    address: "guardian".to_string(),
+                    max_pause: Duration::Time(50),
+                    max_pauses: 2,
+                }),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        let pause = || ExecuteMsg::Pause {
+            reason: "oracle exploit".to_string(),
+        };
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4_000_000).unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "bob", 4_000_000).unwrap();
+
+        let err = exec_at(deps.as_mut(), 10, "creator", pause()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        exec_at(deps.as_mut(), 10, "guardian", pause()).unwrap();
+        let err = contribute(deps.as_mut(), env_at(20, 0), "carol", 1_000_000).unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+        // refunds stay open
+        exec_at(deps.as_mut(), 20, "bob", ExecuteMsg::RefundMsg {}).unwrap();
+
+        // lifted on its own once `max_pause` is over
+        contribute(deps.as_mut(), env_at(60, 0), "carol", 1_000_000).unwrap();
+        exec_at(deps.as_mut(), 95, "guardian", pause()).unwrap();
+        let err = exec_at(deps.as_mut(), 100, "anyone", ExecuteMsg::ResolveMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::Paused {}));
+        exec_at(deps.as_mut(), 110, "guardian", ExecuteMsg::Unpause {}).unwrap();
+        let err = exec_at(deps.as_mut(), 110, "guardian", ExecuteMsg::Unpause {}).unwrap_err();
+        assert!(matches!(err, ContractError::NotPaused {}));
+        // out of pauses, the guardian cannot hold resolution off any longer
+        let err = exec_at(deps.as_mut(), 110, "guardian", pause()).unwrap_err();
+        assert!(matches!(err, ContractError::PauseLimit {}));
+        exec_at(deps.as_mut(), 110, "anyone", ExecuteMsg::ResolveMsg {}).unwrap();
+
+        let res = query::pause(deps.as_ref(), env_at(110, 0)).unwrap();
+        assert!(!res.paused);
+        assert_eq!(2, res.history.len());
+        assert_eq!(None, res.history[0].lifted_at);
+        assert_eq!(
+            Some(mock_env().block.time.plus_seconds(110)),
+            res.history[1].lifted_at
+        );
+    }
+
+    #[test]
+    fn guardian_limits() {
+        let mut deps = mock_dependencies();
+        let with_guardian = |max_pause, max_pauses| InstantiateMsg {
+            guardian: Some(GuardianConfig {
+                address: "guardian".to_string(),
+                max_pause,
+                max_pauses,
+            }),
+            ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+        };
+
+        // a pause too long would overflow the block time
+        for (max_pause, max_pauses) in [
+            (Duration::Time(50), 0),
+            (Duration::Time(50), MAX_PAUSES + 1),
+            (Duration::Time(0), 2),
+            (Duration::Time(u64::MAX), 2),
+            (Duration::Height(MAX_PAUSE_BLOCKS + 1), 2),
+        ] {
+            let err = instantiate(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                with_guardian(max_pause, max_pauses),
+            )
+            .unwrap_err();
+            assert!(matches!(err, ContractError::InvalidGuardian { .. }));
+        }
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            with_guardian(Duration::Time(MAX_PAUSE_SECONDS), MAX_PAUSES),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn governance_sudo() {
+        let mut deps = mock_dependencies();
+        let deadline = Expiration::AtTime(mock_env().block.time.plus_seconds(100));
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            campaign(deadline),
+        )
+        .unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4_000_000).unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "bob", 1_000_000).unwrap();
+
+        let force_cancel = SudoMsg::ForceCancel {
+            reason: "court order".to_string(),
+        };
+        let res = sudo(deps.as_mut(), env_at(10, 0), force_cancel).unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: vec![Coin::new(1_000_000, "OSMO")],
+            }),
+            res.messages[0].msg
+        );
+        assert_eq!(2, res.messages.len());
+        assert_eq!(
+            vec![Event::new("sudo")
+                .add_attribute("action", "force-cancel")
+                .add_attribute("reason", "court order")],
+            res.events
+        );
+        let err = exec_at(deps.as_mut(), 20, "alice", ExecuteMsg::ClaimRefund {}).unwrap_err();
+        assert!(matches!(err, ContractError::NoContribution {}));
+        let err = sudo(deps.as_mut(), env_at(20, 0), SudoMsg::ForceResolve {}).unwrap_err();
+        assert!(matches!(err, ContractError::Cancelled {}));
+
+        // forced resolution does not wait for the deadline
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            campaign(deadline),
+        )
+        .unwrap();
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 10_000_000).unwrap();
+        let res = sudo(deps.as_mut(), env_at(10, 0), SudoMsg::ForceResolve {}).unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: vec![Coin::new(10_000_000, "OSMO")],
+            }),
+            res.messages[0].msg
+        );
+        assert_eq!("force-resolve", res.events[0].attributes[0].value);
+    }
+
+    #[test]
+    fn merkle_allowlist() {
+        let members = [
+            ("alice", Some(Uint128::new(5_000_000))),
+            ("bob", None),
+            ("carol", None),
+        ];
+        let tree = MerkleTree::new(
+            members
+                .iter()
+                .map(|(address, cap)| merkle::leaf(address, *cap))
+                .collect(),
+        );
+        let proof = |index: usize| AllowlistProof {
+            proof: tree.proof(index).into_iter().map(Binary::from).collect(),
+            cap: members[index].1,
+        };
+        let contribute_with = |deps: DepsMut, sender: &str, amount, proof| {
+            let coin = Coin::new(amount, "OSMO");
+            execute(
+                deps,
+                env_at(10, 0),
+                mock_info(sender, std::slice::from_ref(&coin)),
+                ExecuteMsg::ContributionMsg {
+                    coin,
+                    allowlist: proof,
+                    credential: None,
+                    beneficiary: None,
+                },
+            )
+        };
+
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                allowlist_root: Some(Binary::from(tree.root().unwrap())),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+
+        let err = contribute(deps.as_mut(), env_at(10, 0), "dave", 1_000_000).unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted {}));
+        let err = contribute_with(deps.as_mut(), "dave", 1_000_000, Some(proof(1))).unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted {}));
+        contribute_with(deps.as_mut(), "carol", 1_000_000, Some(proof(2))).unwrap();
+        contribute_with(deps.as_mut(), "alice", 4_000_000, Some(proof(0))).unwrap();
+        // the proof is remembered, the cap still applies
+        contribute(deps.as_mut(), env_at(10, 0), "alice", 1_000_000).unwrap();
+        let err = contribute(deps.as_mut(), env_at(10, 0), "alice", 1).unwrap_err();
+        assert!(matches!(err, ContractError::AbovePledgeCap {}));
+        // claiming no cap does not match alice's leaf
+        let uncapped = AllowlistProof {
+            cap: None,
+            ..proof(0)
+        };
+        let err = contribute_with(deps.as_mut(), "alice", 1, Some(uncapped)).unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted {}));
+
+        let update = ExecuteMsg::UpdateAllowlistRoot {
+            root: Some(Binary::from(merkle::leaf("bob", None))),
+        };
+        let err = exec_at(deps.as_mut(), 20, "alice", update.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        exec_at(deps.as_mut(), 20, "creator", update).unwrap();
+        let err = contribute(deps.as_mut(), env_at(20, 0), "carol", 1_000_000).unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted {}));
+        let lone = AllowlistProof {
+            proof: vec![],
+            cap: None,
+        };
+        contribute_with(deps.as_mut(), "bob", 1_000_000, Some(lone)).unwrap();
+    }
+
+    #[test]
+    fn attester_credentials() {
+        let attester = SigningKey::from_bytes(&[7u8; 32]).unwrap();
+        let issue = |address: &str, nonce, expiry_seconds| {
+            let credential = Credential {
+                address: address.to_string(),
+                expiry: mock_env().block.time.plus_seconds(expiry_seconds),
+                max_amount: Uint128::new(3_000_000),
+                nonce,
+            };
+            let digest = credential::digest(MOCK_CONTRACT_ADDR, &credential).unwrap();
+            let signature: Signature = attester.sign_prehash(&digest).unwrap();
+            SignedCredential {
+                credential,
+                signature: Binary::from(signature.as_ref()),
+            }
+        };
+        let contribute_with = |deps: DepsMut, sender: &str, amount, credential| {
+            let coin = Coin::new(amount, "OSMO");
+            execute(
+                deps,
+                env_at(10, 0),
+                mock_info(sender, std::slice::from_ref(&coin)),
+                ExecuteMsg::ContributionMsg {
+                    coin,
+                    allowlist: None,
+                    credential,
+                    beneficiary: None,
+                },
+            )
+        };
+
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                attester: Some(Binary::from(attester.verifying_key().to_bytes().as_slice())),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
 
-        let init_receiver_none = RECEIVER.load(&deps.storage).unwrap();
-        assert_eq!("creator", init_receiver_none);
+        let err = contribute(deps.as_mut(), env_at(10, 0), "alice", 1_000_000).unwrap_err();
+        assert!(matches!(err, ContractError::NotEligible {}));
+        let err = contribute_with(deps.as_mut(), "bob", 1_000_000, Some(issue("alice", 1, 50)))
+            .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCredential { .. }));
+        let err = contribute_with(
+            deps.as_mut(),
+            "alice",
+            1_000_000,
+            Some(issue("alice", 1, 5)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCredential { .. }));
+        let mut forged = issue("alice", 1, 50);
+        forged.credential.max_amount = Uint128::new(100_000_000);
+        let err = contribute_with(deps.as_mut(), "alice", 1_000_000, Some(forged)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCredential { .. }));
 
-        // it worked, let's query the state
-        // let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
-        // let value: GetCountResponse = from_binary(&res).unwrap();
-        // assert_eq!(17, value.count);
+        contribute_with(
+            deps.as_mut(),
+            "alice",
+            1_000_000,
+            Some(issue("alice", 1, 50)),
+        )
+        .unwrap();
+        contribute(deps.as_mut(), env_at(10, 0), "alice", 2_000_000).unwrap();
+        let err = contribute(deps.as_mut(), env_at(10, 0), "alice", 1).unwrap_err();
+        assert!(matches!(err, ContractError::AbovePledgeCap {}));
+        // each credential is accepted once
+        let err =
+            contribute_with(deps.as_mut(), "alice", 1, Some(issue("alice", 1, 50))).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCredential { .. }));
+
+        contribute_with(deps.as_mut(), "bob", 1_000_000, Some(issue("bob", 2, 50))).unwrap();
+        let revoke = ExecuteMsg::RevokeCredential {
+            nonce: 2,
+            signature: None,
+        };
+        let err = exec_at(deps.as_mut(), 20, "bob", revoke.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        exec_at(deps.as_mut(), 20, "creator", revoke).unwrap();
+        let err = contribute(deps.as_mut(), env_at(10, 0), "bob", 1_000_000).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCredential { .. }));
+        let res = query::credential(deps.as_ref(), env_at(20, 0), "bob".to_string()).unwrap();
+        assert!(res.revoked);
+
+        // the attester's signature lets anyone relay a revocation
+        contribute_with(
+            deps.as_mut(),
+            "carol",
+            1_000_000,
+            Some(issue("carol", 3, 50)),
+        )
+        .unwrap();
+        let revoke_signed = |key: &SigningKey, nonce| {
+            let digest = credential::revocation_digest(MOCK_CONTRACT_ADDR, nonce).unwrap();
+            let signature: Signature = key.sign_prehash(&digest).unwrap();
+            ExecuteMsg::RevokeCredential {
+                nonce,
+                signature: Some(Binary::from(signature.as_ref())),
+            }
+        };
+        let mallory = SigningKey::from_bytes(&[9u8; 32]).unwrap();
+        let err = exec_at(deps.as_mut(), 20, "relayer", revoke_signed(&mallory, 3)).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        exec_at(deps.as_mut(), 20, "relayer", revoke_signed(&attester, 3)).unwrap();
+        let res = query::credential(deps.as_ref(), env_at(20, 0), "carol".to_string()).unwrap();
+        assert!(res.revoked);
+        // refunds do not need a valid credential
+        exec_at(deps.as_mut(), 20, "bob", ExecuteMsg::RefundMsg {}).unwrap();
     }
 
-    // #[test]
-    // fn query_usercontribution() {
-    //     let mut deps = mock_dependencies();
+    /// `MockApi` only humanizes its own 54-byte canonical addresses, this one also hex encodes
+    /// the 20-byte ones derived from keys.
+    #[derive(Default)]
+    struct KeyApi(MockApi);
 
-    //     instantiate(
-    //         deps.as_mut(),
-    //         mock_env(),
-    //         mock_info("creator", &[]),
-    //         InstantiateMsg {
-    //             coin_threshold: Coin {
-    //                 denom: "OSMO".to_string(),
-    //                 amount: Uint128::from(10_000_000u128),
-    //             },
-    //             deadline: Timestamp::from_seconds(10),
-    //             receiver: None,
-    //         },
-    //     )
-    //     .unwrap();
+    impl Api for KeyApi {
+        fn addr_validate(&self, human: &str) -> StdResult<Addr> {
+            self.0.addr_validate(human)
+        }
 
-    //     execute(
-    //         deps.as_mut(),
-    //         mock_env(),
-    //         mock_info("anyone", &[]),
-    //         ExecuteMsg::ContributionMsg {
-    //             coin: Coin {
-    //                 denom: "OSMO".to_string(),
-    //                 amount: Uint128::from(1_000_000u128),
-    //             },
-    //         },
-    //     )
-    //     .unwrap();
+        fn addr_canonicalize(&self, human: &str) -> StdResult<CanonicalAddr> {
+            self.0.addr_canonicalize(human)
+        }
 
-    //     let _res = query(
-    //         deps.as_ref(),
-    //         mock_env(),
-    //         QueryMsg::GetUserContribution {
-    //             addr: "anyone".to_string(),
-    //         },
-    //     )
-    //     .unwrap();
-    //     // let err = from_binary(&res).unwrap_err();
-    //     // assert that value is of Error DeadlinePassed
-    //     // assert_eq!(ContractError::DeadlinePassed {}, err.downcast().unwrap());
-    //     // assert_eq!(Uint128::from(1_000_000u128), value.amount.unwrap());
-    // }
+        fn addr_humanize(&self, canonical: &CanonicalAddr) -> StdResult<Addr> {
+            if canonical.len() != 20 {
+                return self.0.addr_humanize(canonical);
+            }
+            let hex: String = canonical.iter().map(|b| format!("{:02x}", b)).collect();
+            Ok(Addr::unchecked(hex))
+        }
 
-    // #[test]
-    // fn onecontribution() {
-    //     let (mut app, contract_addr) = contract_builder(Timestamp::from_seconds(10));
+        fn secp256k1_verify(
+            &self,
+            message_hash: &[u8],
+            signature: &[u8],
+            public_key: &[u8],
+        ) -> Result<bool, VerificationError> {
+            self.0.secp256k1_verify(message_hash, signature, public_key)
+        }
 
-    //     let c = Coin {
-    //         denom: String::from("OSMO"),
-    //         amount: Uint128::from(1000u128),
-    //     };
+        fn secp256k1_recover_pubkey(
+            &self,
+            message_hash: &[u8],
+            signature: &[u8],
+            recovery_param: u8,
+        ) -> Result<Vec<u8>, RecoverPubkeyError> {
+            self.0
+                .secp256k1_recover_pubkey(message_hash, signature, recovery_param)
+        }
 
-    //     let r1_addr = Addr::unchecked("sender1");
-    //     let r1 = app.execute_contract(
-    //         r1_addr.clone(),
-    //         contract_addr.clone(),
-    //         &ExecuteMsg::ContributionMsg { coin: c.clone() },
-    //         &[c.clone(), c.clone(), c.clone()],
-    //     );
+        fn ed25519_verify(
+            &self,
+            message: &[u8],
+            signature: &[u8],
+            public_key: &[u8],
+        ) -> Result<bool, VerificationError> {
+            self.0.ed25519_verify(message, signature, public_key)
+        }
 
-    //     dbg!(&r1);
-    //     // assert!(r1.is_ok());
+        fn ed25519_batch_verify(
+            &self,
+            messages: &[&[u8]],
+            signatures: &[&[u8]],
+            public_keys: &[&[u8]],
+        ) -> Result<bool, VerificationError> {
+            self.0
+                .ed25519_batch_verify(messages, signatures, public_keys)
+        }
 
-    //     let query_resp: ContributionResponse = app
-    //         .wrap()
-    //         .query_wasm_smart(
-    //             contract_addr,
-    //             &QueryMsg::GetUserContribution {
-    //                 addr: r1_addr.into_string(),
-    //             },
-    //         )
-    //         .unwrap();
+        fn debug(&self, message: &str) {
+            self.0.debug(message)
+        }
+    }
 
-    //     assert_eq!(
-    //         query_resp,
-    //         ContributionResponse {
-    //             amount: Uint128::from(1000u128),
-    //         }
-    //     );
-    // }
+    fn key_dependencies() -> OwnedDeps<MockStorage, KeyApi, MockQuerier> {
+        OwnedDeps {
+            storage: MockStorage::default(),
+            api: KeyApi::default(),
+            querier: MockQuerier::default(),
+            custom_query_type: PhantomData,
+        }
+    }
 
     #[test]
-    fn totalcontribution_0() {
-        let mut app = App::default();
-        let contract_id = app.store_code(contract());
-        let contract_addr = app
+    fn relayed_intents() {
+        let mut deps = key_dependencies();
+        deps.querier.update_wasm(|_| {
+            let allowance = AllowanceResponse {
+                allowance: Uint128::new(5_000_000),
+                expires: Expiration::Never {},
+            };
+            SystemResult::Ok(ContractResult::Ok(to_binary(&allowance).unwrap()))
+        });
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                accepted_denoms: Some(vec![DenomWeight {
+                    denom: "token".to_string(),
+                    weight: Decimal::one(),
+                }]),
+                cw20: Some("token".to_string()),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        let alice = SigningKey::from_bytes(&[3u8; 32]).unwrap();
+        let pubkey = Binary::from(alice.verifying_key().to_bytes().as_slice());
+        // the pledge goes to the key's own account
+        let contributor = intent::contributor(&deps.api, &pubkey).unwrap();
+        let sign = |amount: Coin, nonce| {
+            let intent = PledgeIntent {
+                campaign: MOCK_CONTRACT_ADDR.to_string(),
+                amount,
+                nonce,
+                expiry: mock_env().block.time.plus_seconds(50),
+            };
+            let signature: Signature = alice
+                .sign_prehash(&intent::digest(&intent).unwrap())
+                .unwrap();
+            SignedIntent {
+                intent,
+                pubkey: pubkey.clone(),
+                signature: Binary::from(signature.as_ref()),
+            }
+        };
+        let submit = |deps: DepsMut, funds: &[Coin], signed| {
+            execute(
+                deps,
+                env_at(10, 0),
+                mock_info("relayer", funds),
+                ExecuteMsg::SubmitIntent(signed),
+            )
+        };
+
+        // the relayer fronts native pledges
+        let osmo = Coin::new(2_000_000, "OSMO");
+        let err = submit(deps.as_mut(), &[], sign(osmo.clone(), 1)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIntent { .. }));
+        let mut uncompressed = sign(osmo.clone(), 1);
+        uncompressed.pubkey =
+            Binary::from(alice.verifying_key().to_encoded_point(false).as_bytes());
+        let err = submit(deps.as_mut(), std::slice::from_ref(&osmo), uncompressed).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIntent { .. }));
+        submit(
+            deps.as_mut(),
+            std::slice::from_ref(&osmo),
+            sign(osmo.clone(), 1),
+        )
+        .unwrap();
+        let err = submit(
+            deps.as_mut(),
+            std::slice::from_ref(&osmo),
+            sign(osmo.clone(), 1),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidIntent { .. }));
+        let res = query::usercontribution(deps.as_ref(), env_at(10, 0), contributor.to_string());
+        assert_eq!(Uint128::new(2_000_000), res.unwrap().amount);
+
+        // cw20 pledges draw on the key account's allowance
+        let token = Coin::new(3_000_000, "token");
+        submit(deps.as_mut(), &[], sign(token, 2)).unwrap();
+        let pledge = query::pledge(deps.as_ref(), env_at(10, 0), contributor.to_string()).unwrap();
+        assert_eq!(Uint128::new(3_000_000), pledge.amount);
+        let nonce = query::intent_nonce(deps.as_ref(), env_at(10, 0), pubkey).unwrap();
+        assert_eq!(Some(2), nonce.nonce);
+
+        // what the relayer fronted stays with the pledge and is refunded to it
+        let transfer = ExecuteMsg::TransferPledge {
+            to: "bob".to_string(),
+            amount: osmo.clone(),
+            allowlist: None,
+            credential: None,
+        };
+        let err = exec_at(deps.as_mut(), 20, contributor.as_str(), transfer).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTransfer {}));
+        let res = exec_at(
+            deps.as_mut(),
+            20,
+            contributor.as_str(),
+            ExecuteMsg::RefundMsg {},
+        )
+        .unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: "relayer".to_string(),
+                amount: vec![osmo],
+            })],
+            res.messages.into_iter().map(|m| m.msg).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn relayed_intent_objection() {
+        let mut deps = key_dependencies();
+        extensible_campaign(deps.as_mut(), 1);
+        let alice = SigningKey::from_bytes(&[3u8; 32]).unwrap();
+        let pubkey = Binary::from(alice.verifying_key().to_bytes().as_slice());
+        let contributor = intent::contributor(&deps.api, &pubkey).unwrap();
+        let osmo = Coin::new(2_000_000, "OSMO");
+        let intent = PledgeIntent {
+            campaign: MOCK_CONTRACT_ADDR.to_string(),
+            amount: osmo.clone(),
+            nonce: 1,
+            expiry: mock_env().block.time.plus_seconds(50),
+        };
+        let signature: Signature = alice
+            .sign_prehash(&intent::digest(&intent).unwrap())
+            .unwrap();
+        let signed = SignedIntent {
+            intent,
+            pubkey,
+            signature: Binary::from(signature.as_ref()),
+        };
+        execute(
+            deps.as_mut(),
+            env_at(10, 0),
+            mock_info("relayer", std::slice::from_ref(&osmo)),
+            ExecuteMsg::SubmitIntent(signed),
+        )
+        .unwrap();
+        let sent = |res: Response| -> Vec<CosmosMsg> {
+            res.messages.into_iter().map(|submsg| submsg.msg).collect()
+        };
+
+        // objecting gives the relayer back what it fronted
+        let propose = ExecuteMsg::ProposeExtension {
+            deadline: Expiration::AtTime(mock_env().block.time.plus_seconds(200)),
+        };
+        exec_at(deps.as_mut(), 50, "creator", propose).unwrap();
+        let res = exec_at(
+            deps.as_mut(),
+            55,
+            contributor.as_str(),
+            ExecuteMsg::ObjectExtension {},
+        )
+        .unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: "relayer".to_string(),
+                amount: vec![osmo],
+            })],
+            sent(res)
+        );
+
+        // what the contributor pledges afterwards is its own to move or take back
+        contribute(
+            deps.as_mut(),
+            env_at(56, 0),
+            contributor.as_str(),
+            1_000_000,
+        )
+        .unwrap();
+        let transfer = ExecuteMsg::TransferPledge {
+            to: "bob".to_string(),
+            amount: Coin::new(500_000, "OSMO"),
+            allowlist: None,
+            credential: None,
+        };
+        exec_at(deps.as_mut(), 56, contributor.as_str(), transfer).unwrap();
+        let res = exec_at(
+            deps.as_mut(),
+            57,
+            contributor.as_str(),
+            ExecuteMsg::RefundMsg {},
+        )
+        .unwrap();
+        assert_eq!(
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                to_address: contributor.to_string(),
+                amount: vec![Coin::new(500_000, "OSMO")],
+            })],
+            sent(res)
+        );
+    }
+
+    #[test]
+    fn beneficiaries() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100))),
+        )
+        .unwrap();
+        let coin = Coin::new(1_000_000, "OSMO");
+        execute(
+            deps.as_mut(),
+            env_at(10, 0),
+            mock_info("multisig", std::slice::from_ref(&coin)),
+            ExecuteMsg::ContributionMsg {
+                coin,
+                allowlist: None,
+                credential: None,
+                beneficiary: Some("alice".to_string()),
+            },
+        )
+        .unwrap();
+
+        let batch = ExecuteMsg::ContributionBatch {
+            contributions: vec![
+                BeneficiaryContribution {
+                    beneficiary: "bob".to_string(),
+                    coin: Coin::new(2_000_000, "OSMO"),
+                },
+                BeneficiaryContribution {
+                    beneficiary: "carol".to_string(),
+                    coin: Coin::new(3_000_000, "OSMO"),
+                },
+            ],
+        };
+        let info = mock_info("employer", &[Coin::new(4_000_000, "OSMO")]);
+        let err = execute(deps.as_mut(), env_at(10, 0), info, batch.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::CustomError { .. }));
+        // the funds match, but an entry pledges nothing or a beneficiary comes twice
+        let entry = |beneficiary: &str, amount| BeneficiaryContribution {
+            beneficiary: beneficiary.to_string(),
+            coin: Coin::new(amount, "OSMO"),
+        };
+        for contributions in [
+            vec![entry("bob", 2_000_000), entry("carol", 0)],
+            vec![entry("bob", 1_000_000), entry("bob", 1_000_000)],
+        ] {
+            let info = mock_info("employer", &[Coin::new(2_000_000, "OSMO")]);
+            let msg = ExecuteMsg::ContributionBatch { contributions };
+            let err = execute(deps.as_mut(), env_at(10, 0), info, msg).unwrap_err();
+            assert!(matches!(err, ContractError::CustomError { .. }));
+        }
+        let info = mock_info("employer", &[Coin::new(5_000_000, "OSMO")]);
+        execute(deps.as_mut(), env_at(10, 0), info, batch).unwrap();
+
+        let contribution = |addr: &str| {
+            query::usercontribution(deps.as_ref(), env_at(10, 0), addr.to_string())
+                .unwrap()
+                .amount
+        };
+        assert_eq!(Uint128::new(1_000_000), contribution("alice"));
+        assert_eq!(Uint128::new(3_000_000), contribution("carol"));
+        assert_eq!(Uint128::zero(), contribution("employer"));
+        // refunds go to the beneficiary
+        let res = exec_at(deps.as_mut(), 20, "bob", ExecuteMsg::RefundMsg {}).unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "bob".to_string(),
+                amount: vec![Coin::new(2_000_000, "OSMO")],
+            }),
+            res.messages[0].msg
+        );
+        let err = exec_at(deps.as_mut(), 20, "employer", ExecuteMsg::RefundMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::CustomError { .. }));
+    }
+
+    #[test]
+    fn pledge_transfers() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                min_contribution: Some(Uint128::new(1_000_000)),
+                refund_policy: Some(RefundPolicy::LockOnThreshold {}),
+                ..campaign(Expiration::AtTime(mock_env().block.time.plus_seconds(100)))
+            },
+        )
+        .unwrap();
+        let transfer = |to: &str, amount| ExecuteMsg::TransferPledge {
+            to: to.to_string(),
+            amount: Coin::new(amount, "OSMO"),
+            allowlist: None,
+            credential: None,
+        };
+        contribute(deps.as_mut(), env_at(0, 0), "alice", 4_000_000).unwrap();
+
+        let err = exec_at(deps.as_mut(), 10, "alice", transfer("bob", 5_000_000)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTransfer {}));
+        let err = exec_at(deps.as_mut(), 10, "alice", transfer("alice", 1_000_000)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTransfer {}));
+        // both sides must still meet the minimum
+        let err = exec_at(deps.as_mut(), 10, "alice", transfer("bob", 3_500_000)).unwrap_err();
+        assert!(matches!(err, ContractError::ContributionTooLow {}));
+        exec_at(deps.as_mut(), 10, "alice", transfer("bob", 1_500_000)).unwrap();
+        // a rotated wallet keeps its pledge past the refund lock
+        contribute(deps.as_mut(), env_at(20, 0), "carol", 6_000_000).unwrap();
+        let err = exec_at(deps.as_mut(), 30, "alice", ExecuteMsg::RefundMsg {}).unwrap_err();
+        assert!(matches!(err, ContractError::RefundsLocked {}));
+        exec_at(deps.as_mut(), 30, "alice", transfer("alice-new", 2_500_000)).unwrap();
+
+        let contribution = |addr: &str| {
+            query::usercontribution(deps.as_ref(), env_at(30, 0), addr.to_string())
+                .unwrap()
+                .amount
+        };
+        assert_eq!(Uint128::zero(), contribution("alice"));
+        assert_eq!(Uint128::new(2_500_000), contribution("alice-new"));
+        assert_eq!(Uint128::new(1_500_000), contribution("bob"));
+        let err = exec_at(deps.as_mut(), 100, "alice", transfer("bob", 1)).unwrap_err();
+        assert!(matches!(err, ContractError::DeadlinePassed {}));
+    }
+
+    /// Oracle publishing the prices it is given, stamped with the block time.
+    mod mock_oracle {
+        use crate::oracle::{OracleQueryMsg, PriceResponse};
+        use cosmwasm_schema::cw_serde;
+        use cosmwasm_std::{
+            to_binary, Binary, Decimal, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult,
+        };
+        use cw_storage_plus::Map;
+
+        const PRICES: Map<&str, PriceResponse> = Map::new("prices");
+
+        #[cw_serde]
+        pub struct SetPrice {
+            pub denom: String,
+            pub price: Decimal,
+        }
+
+        pub fn instantiate(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+            Ok(Response::new())
+        }
+
+        pub fn execute(
+            deps: DepsMut,
+            env: Env,
+            _: MessageInfo,
+            msg: SetPrice,
+        ) -> StdResult<Response> {
+            let price = PriceResponse {
+                price: msg.price,
+                updated_at: env.block.time,
+            };
+            PRICES.save(deps.storage, &msg.denom, &price)?;
+            Ok(Response::new())
+        }
+
+        pub fn query(deps: Deps, _: Env, msg: OracleQueryMsg) -> StdResult<Binary> {
+            match msg {
+                OracleQueryMsg::Price { denom, .. } => {
+                    to_binary(&PRICES.load(deps.storage, &denom)?)
+                }
+            }
+        }
+    }
+
+    /// Campaign priced by `mock_oracle` at ATOM = 10 USD, OSMO = 1 USD, with alice's 5 ATOM and
+    /// bob's 30 OSMO pledged. Returns the app, the oracle and the campaign.
+    fn oracle_campaign() -> (App, Addr, Addr) {
+        let mut app = AppBuilder::new().build(|router, _, storage| {
+            for (user, coin) in [
+                ("alice", Coin::new(5, "ATOM")),
+                ("bob", Coin::new(30, "OSMO")),
+            ] {
+                router
+                    .bank
+                    .init_balance(storage, &Addr::unchecked(user), vec![coin])
+                    .unwrap();
+            }
+        });
+        let oracle_id = app.store_code(Box::new(ContractWrapper::new(
+            mock_oracle::execute,
+            mock_oracle::instantiate,
+            mock_oracle::query,
+        )));
+        let oracle = app
             .instantiate_contract(
-                contract_id,
-                Addr::unchecked("sender"),
+                oracle_id,
+                Addr::unchecked("owner"),
+                &Empty {},
+                &[],
+                "Oracle",
+                None,
+            )
+            .unwrap();
+        set_price(&mut app, &oracle, "ATOM", 10);
+        set_price(&mut app, &oracle, "OSMO", 1);
+
+        let campaign_id = app.store_code(contract());
+        let deadline = app.block_info().time.plus_seconds(100);
+        let campaign = app
+            .instantiate_contract(
+                campaign_id,
+                Addr::unchecked("creator"),
                 &InstantiateMsg {
-                    coin_threshold: Coin {
-                        denom: "OSMO".to_string(),
-                        amount: Uint128::from(10_000_000u128),
-                    },
-                    deadline: Timestamp::from_seconds(10),
-                    receiver: None,
+                    coin_threshold: Coin::new(100, "USD"),
+                    oracle: Some(OracleConfig {
+                        contract: oracle.to_string(),
+                        denoms: vec!["ATOM".to_string(), "OSMO".to_string()],
+                        max_staleness: 60,
+                    }),
+                    ..campaign(Expiration::AtTime(deadline))
                 },
                 &[],
                 "Threshold Funding",
                 None,
             )
             .unwrap();
-
-        let resp: ContributionResponse = app
+        for (user, coin) in [
+            ("alice", Coin::new(5, "ATOM")),
+            ("bob", Coin::new(30, "OSMO")),
+        ] {
+            app.execute_contract(
+                Addr::unchecked(user),
+                campaign.clone(),
+                &ExecuteMsg::ContributionMsg {
+                    coin: coin.clone(),
+                    allowlist: None,
+                    credential: None,
+                    beneficiary: None,
+                },
+                &[coin],
+            )
+            .unwrap();
+        }
+        let status: StatusResponse = app
             .wrap()
-            .query_wasm_smart(contract_addr, &QueryMsg::GetTotalContribution {})
+            .query_wasm_smart(&campaign, &QueryMsg::GetStatus {})
             .unwrap();
+        assert_eq!(Coin::new(80, "USD"), status.total);
+        (app, oracle, campaign)
+    }
 
-        assert_eq!(
-            resp,
-            ContributionResponse {
-                amount: Uint128::from(0u128),
-            }
-        );
+    fn set_price(app: &mut App, oracle: &Addr, denom: &str, price: u128) {
+        let msg = mock_oracle::SetPrice {
+            denom: denom.to_string(),
+            price: Decimal::from_ratio(price, 1u128),
+        };
+        app.execute_contract(Addr::unchecked("owner"), oracle.clone(), &msg, &[])
+            .unwrap();
     }
 
-    // #[test]
-    // fn totalcontribution_100() {
-    //     let mut app = App::default();
-    //     let contract_id = app.store_code(contract());
-    //     let contract_addr = app
-    //         .instantiate_contract(
-    //             contract_id,
-    //             Addr::unchecked("sender"),
-    //             &InstantiateMsg {
-    //                 coin_threshold: Coin {
-    //                     denom: "OSMO".to_string(),
-    //                     amount: Uint128::from(10_000_000u128),
-    //                 },
-    //                 deadline: Timestamp::from_seconds(10),
-    //                 receiver: None,
-    //             },
-    //             &[],
-    //             "Threshold Funding",
-    //             None,
-    //         )
-    //         .unwrap();
+    #[test]
+    fn oracle_priced_threshold() {
+        let (mut app, oracle, campaign) = oracle_campaign();
 
-    //     let c = Coin {
-    //         denom: "OSMO".to_string(),
-    //         amount: Uint128::from(50u128),
-    //     };
-    //     let _r1 = app.execute_contract(
-    //         Addr::unchecked("sender1"),
-    //         contract_addr.clone(),
-    //         &ExecuteMsg::ContributionMsg { coin: c.clone() },
-    //         &[c.clone()],
-    //     );
+        // ATOM went up, the snapshot taken at resolution reaches the threshold
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        set_price(&mut app, &oracle, "ATOM", 14);
+        set_price(&mut app, &oracle, "OSMO", 1);
+        app.execute_contract(
+            Addr::unchecked("anyone"),
+            campaign,
+            &ExecuteMsg::ResolveMsg {},
+            &[],
+        )
+        .unwrap();
+        let balances = app.wrap().query_all_balances("creator").unwrap();
+        assert_eq!(vec![Coin::new(5, "ATOM"), Coin::new(30, "OSMO")], balances);
+    }
 
-    //     let _r2 = app.execute_contract(
-    //         Addr::unchecked("sender2"),
-    //         contract_addr.clone(),
-    //         &ExecuteMsg::ContributionMsg { coin: c.clone() },
-    //         &[c.clone()],
-    //     );
+    #[test]
+    fn dead_oracle_refunds() {
+        let (mut app, _, campaign) = oracle_campaign();
 
-    //     let resp: ContributionResponse = app
-    //         .wrap()
-    //         .query_wasm_smart(contract_addr, &QueryMsg::GetTotalContribution {})
-    //         .unwrap();
-    //     assert_eq!(
-    //         resp,
-    //         ContributionResponse {
-    //             amount: Uint128::from(100u128),
-    //         }
-    //     );
-    // }
+        // the oracle stopped publishing, its prices are stale by the deadline
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        let res = app
+            .execute_contract(
+                Addr::unchecked("anyone"),
+                campaign,
+                &ExecuteMsg::ResolveMsg {},
+                &[],
+            )
+            .unwrap();
+        let wasm = res.events.iter().find(|e| e.ty == "wasm").unwrap();
+        for (key, value) in [("status", "refund"), ("reason", "stale-prices")] {
+            assert!(wasm
+                .attributes
+                .iter()
+                .any(|a| a.key == key && a.value == value));
+        }
+        let balances = |user: &str| app.wrap().query_all_balances(user).unwrap();
+        assert_eq!(vec![Coin::new(5, "ATOM")], balances("alice"));
+        assert_eq!(vec![Coin::new(30, "OSMO")], balances("bob"));
+        assert!(balances("creator").is_empty());
+    }
 
     // TODO test when doing a contribution, the declared amount is equal to the sent amount
 
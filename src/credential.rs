@@ -0,0 +1,59 @@
+//! Eligibility credentials signed by the campaign's attester, e.g. a KYC provider.
+//!
+//! The attester signs with secp256k1 the sha256 of the JSON serialization of a
+//! `CredentialPayload`, which binds the credential to a single campaign. It revokes one the
+//! same way, signing a `Revocation`.
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{to_vec, Binary, StdResult, Timestamp, Uint128};
+use sha2::{Digest, Sha256};
+
+#[cw_serde]
+pub struct Credential {
+    /// Contributor the credential is issued to.
+    pub address: String,
+    pub expiry: Timestamp,
+    /// Most the contributor may pledge, in the threshold's denom.
+    pub max_amount: Uint128,
+    /// Identifies the credential, each one is accepted once.
+    pub nonce: u64,
+}
+
+#[cw_serde]
+pub struct SignedCredential {
+    pub credential: Credential,
+    /// 64-byte secp256k1 signature of the credential's `digest`.
+    pub signature: Binary,
+}
+
+#[cw_serde]
+pub struct CredentialPayload {
+    /// Address of the campaign contract.
+    pub campaign: String,
+    pub credential: Credential,
+}
+
+/// Hash the attester signs for `credential` to be accepted by `campaign`.
+pub fn digest(campaign: &str, credential: &Credential) -> StdResult<[u8; 32]> {
+    let payload = CredentialPayload {
+        campaign: campaign.to_string(),
+        credential: credential.clone(),
+    };
+    Ok(Sha256::digest(to_vec(&payload)?).into())
+}
+
+#[cw_serde]
+pub struct Revocation {
+    /// Address of the campaign contract.
+    pub campaign: String,
+    /// Credential revoked.
+    pub nonce: u64,
+}
+
+/// Hash the attester signs for `campaign` to revoke credential `nonce`.
+pub fn revocation_digest(campaign: &str, nonce: u64) -> StdResult<[u8; 32]> {
+    let revocation = Revocation {
+        campaign: campaign.to_string(),
+        nonce,
+    };
+    Ok(Sha256::digest(to_vec(&revocation)?).into())
+}
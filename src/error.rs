@@ -12,9 +12,123 @@ pub enum ContractError {
     #[error("Deadline has passed")]
     DeadlinePassed {},
 
-    #[error("Contribution too low")] // unused
+    #[error("Deadline has not been reached yet")]
+    DeadlineNotReached {},
+
+    #[error("Contributions are not open yet")]
+    NotOpen {},
+
+    #[error("Campaign is waiting for its receivers to accept the role")]
+    Pending {},
+
+    #[error("Campaign is paused")]
+    Paused {},
+
+    #[error("Campaign is not paused")]
+    NotPaused {},
+
+    #[error("Guardian used all of its pauses")]
+    PauseLimit {},
+
+    #[error("Invalid guardian: {reason}")]
+    InvalidGuardian { reason: String },
+
+    #[error("Invalid deadline: {reason}")]
+    InvalidDeadline { reason: String },
+
+    #[error("Contribution too low")]
     ContributionTooLow {},
 
+    #[error("Address is not on the allowlist")]
+    NotAllowlisted {},
+
+    #[error("Pledge is above the address' cap")]
+    AbovePledgeCap {},
+
+    #[error("Merkle root must be 32 bytes")]
+    InvalidMerkleRoot {},
+
+    #[error("Attester key must be a 33 or 65 bytes secp256k1 public key")]
+    InvalidAttester {},
+
+    #[error("A credential from the attester is required")]
+    NotEligible {},
+
+    #[error("Invalid credential: {reason}")]
+    InvalidCredential { reason: String },
+
+    #[error("Invalid pledge intent: {reason}")]
+    InvalidIntent { reason: String },
+
+    #[error("Withdrawal must be positive and at most the contribution")]
+    InvalidWithdrawal {},
+
+    #[error("Transfer must be positive, at most the pledge and to another address")]
+    InvalidTransfer {},
+
+    #[error("Refunds are locked once the threshold is reached")]
+    RefundsLocked {},
+
+    #[error("Denom {denom} is not accepted")]
+    UnacceptedDenom { denom: String },
+
+    #[error("Invalid weight for denom {denom}")]
+    InvalidDenomWeight { denom: String },
+
+    #[error("No cw20 token is accepted")]
+    NoCw20Token {},
+
+    #[error("Allowance does not cover the pledge")]
+    InsufficientAllowance {},
+
+    #[error("Invalid receivers: {reason}")]
+    InvalidReceivers { reason: String },
+
+    #[error("Invalid fee: {reason}")]
+    InvalidFee { reason: String },
+
+    #[error("Invalid action: {reason}")]
+    InvalidAction { reason: String },
+
+    #[error("No failed payout for this recipient")]
+    NoFailedPayout {},
+
+    #[error("Campaign is already resolved")]
+    AlreadyResolved {},
+
+    #[error("Campaign is cancelled")]
+    Cancelled {},
+
+    #[error("Campaign is not cancelled")]
+    NotCancelled {},
+
+    #[error("Campaign can no longer be cancelled")]
+    CancelClosed {},
+
+    #[error("Price of {denom} is stale")]
+    StalePrice { denom: String },
+
+    #[error("Invalid oracle: {reason}")]
+    InvalidOracle { reason: String },
+
+    #[error("Invalid refund policy: {reason}")]
+    InvalidRefundPolicy { reason: String },
+
+    #[error("Invalid extension: {reason}")]
+    InvalidExtension { reason: String },
+
+    #[error("No extension is pending")]
+    NoPendingExtension {},
+
+    #[error("Already voted on this extension")]
+    AlreadyVoted {},
+
+    #[error("No contribution found")]
+    NoContribution {},
+
+    #[error("Invalid threshold schedule: {reason}")]
+    InvalidThresholdSchedule { reason: String },
+
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
     // Add any other custom errors you like here.
@@ -0,0 +1,17 @@
+//! Interface of the factory instantiating campaigns, from which they can inherit a platform fee.
+use cosmwasm_schema::{cw_serde, QueryResponses};
+
+use crate::state::FeeConfig;
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum FactoryQueryMsg {
+    #[returns(FactoryConfigResponse)]
+    Config {},
+}
+
+#[cw_serde]
+pub struct FactoryConfigResponse {
+    /// Fee taken from the campaigns it instantiates, if any.
+    pub fee: Option<FeeConfig>,
+}
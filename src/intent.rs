@@ -0,0 +1,48 @@
+//! Pledge intents signed off-chain by contributors and submitted by a relayer.
+//!
+//! The contributor signs with secp256k1 the sha256 of the JSON serialization of the
+//! `PledgeIntent`, and the pledge is recorded under the account of the signing key. For a
+//! native denom the relayer sends the funds along and gets back what it fronted if the
+//! pledge is refunded. On success the fronted funds go to the receivers like any pledge, the
+//! contract does not reimburse the relayer: that is for the contributor to settle with it
+//! off-chain. For the cw20 token the amount is pledged from the contributor's allowance.
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    to_vec, Addr, Api, Binary, CanonicalAddr, Coin, StdError, StdResult, Timestamp,
+};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+#[cw_serde]
+pub struct PledgeIntent {
+    /// Address of the campaign contract.
+    pub campaign: String,
+    pub amount: Coin,
+    /// Above every nonce the signer used before.
+    pub nonce: u64,
+    pub expiry: Timestamp,
+}
+
+#[cw_serde]
+pub struct SignedIntent {
+    pub intent: PledgeIntent,
+    /// Compressed secp256k1 public key of the signer.
+    pub pubkey: Binary,
+    /// 64-byte secp256k1 signature of the intent's `digest`.
+    pub signature: Binary,
+}
+
+/// Hash the contributor signs.
+pub fn digest(intent: &PledgeIntent) -> StdResult<[u8; 32]> {
+    Ok(Sha256::digest(to_vec(intent)?).into())
+}
+
+/// Account of the compressed `pubkey`, derived like the chain does: the ripemd160 of its
+/// sha256.
+pub fn contributor(api: &dyn Api, pubkey: &[u8]) -> StdResult<Addr> {
+    if pubkey.len() != 33 {
+        return Err(StdError::generic_err("the key must be compressed"));
+    }
+    let hash = Ripemd160::digest(Sha256::digest(pubkey));
+    api.addr_humanize(&CanonicalAddr::from(hash.as_slice()))
+}
@@ -1,8 +1,15 @@
 pub mod contract;
+pub mod credential;
 mod error;
+pub mod factory;
 pub mod helpers;
 pub mod integration_tests;
+pub mod intent;
+pub mod merkle;
 pub mod msg;
+pub mod oracle;
 pub mod state;
 
 pub use crate::error::ContractError;
+/// Interface for receiver contracts, also published on its own for them to depend on.
+pub use threshold_action_hook as hook;
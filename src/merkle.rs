@@ -0,0 +1,73 @@
+//! Merkle tree of the contributor allowlist.
+//!
+//! A leaf is the sha256 of `address`, or of `address:cap` for a capped address. A node is
+//! the sha256 of its two children in ascending order, so proofs need no left/right flags.
+//! The campaign only verifies proofs, `MerkleTree` builds the root and the proofs off-chain.
+use cosmwasm_std::Uint128;
+use sha2::{Digest, Sha256};
+
+pub type Hash = [u8; 32];
+
+pub fn leaf(address: &str, cap: Option<Uint128>) -> Hash {
+    let entry = match cap {
+        Some(cap) => format!("{}:{}", address, cap),
+        None => address.to_string(),
+    };
+    Sha256::digest(entry.as_bytes()).into()
+}
+
+fn parent(a: &Hash, b: &Hash) -> Hash {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update(first);
+    hasher.update(second);
+    hasher.finalize().into()
+}
+
+/// Whether `proof` leads from `leaf` up to `root`.
+pub fn verify(root: &[u8], leaf: Hash, proof: &[Hash]) -> bool {
+    let computed = proof
+        .iter()
+        .fold(leaf, |node, sibling| parent(&node, sibling));
+    computed == root
+}
+
+pub struct MerkleTree {
+    /// Leaves first, root last.
+    layers: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Tree over `leaves` in the given order. A node without sibling moves up unchanged.
+    pub fn new(leaves: Vec<Hash>) -> Self {
+        let mut layers = vec![leaves];
+        while let Some(layer) = layers.last().filter(|layer| layer.len() > 1) {
+            let next = layer
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => parent(a, b),
+                    _ => pair[0],
+                })
+                .collect();
+            layers.push(next);
+        }
+        MerkleTree { layers }
+    }
+
+    /// None for an empty tree.
+    pub fn root(&self) -> Option<Hash> {
+        self.layers.last()?.first().copied()
+    }
+
+    /// Siblings on the way from the leaf at `index` up to the root.
+    pub fn proof(&self, mut index: usize) -> Vec<Hash> {
+        let mut proof = vec![];
+        for layer in &self.layers[..self.layers.len() - 1] {
+            if let Some(sibling) = layer.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        proof
+    }
+}
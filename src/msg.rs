@@ -1,11 +1,60 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Coin, Timestamp};
+use cosmwasm_std::{Addr, Binary, Coin, CosmosMsg, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw_utils::{Expiration, Scheduled};
+
+use crate::credential::{Credential, SignedCredential};
+use crate::intent::SignedIntent;
+use crate::state::{
+    AntiSnipeConfig, CancelWindow, DenomWeight, ExtensionConfig, ExtensionProposal, FeeConfig,
+    FeeSource, GuardianConfig, OracleConfig, Outcome, Pause, ReceiverProposal, ReceiverShare,
+    RefundPolicy, ThresholdSchedule,
+};
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub coin_threshold: Coin,
-    pub deadline: Timestamp,
-    pub receiver: Option<String>, // contract creator if None
+    pub deadline: Expiration,
+    pub opening: Option<Scheduled>, // open right away if None
+    pub receiver: Option<String>,   // contract creator if None
+    /// Several receivers splitting the reward, shares summing to 10000 bps. Excludes `receiver`.
+    pub receivers: Option<Vec<ReceiverShare>>,
+    pub threshold_schedule: Option<ThresholdSchedule>, // constant if None
+    pub extensions: Option<ExtensionConfig>,           // fixed deadline if None
+    pub anti_snipe: Option<AntiSnipeConfig>,           // no automatic extension if None
+    pub refund_policy: Option<RefundPolicy>,           // free refunds if None
+    pub min_contribution: Option<Uint128>,             // no minimum if None
+    pub accepted_denoms: Option<Vec<DenomWeight>>,     // threshold denom only if None
+    /// Prices contributions through an oracle instead, the threshold's denom is then
+    /// only a reference unit and `accepted_denoms` must be None.
+    pub oracle: Option<OracleConfig>,
+    /// cw20 contract accepted through `Receive`. Its address is used as its denom, so it must
+    /// be the threshold's denom or an accepted one.
+    pub cw20: Option<String>,
+    pub receiver_hook: Option<bool>, // bank send to the receiver if None
+    /// Bank sends and wasm messages executed on success, spending from the collected funds
+    /// before the receiver gets the rest.
+    pub actions: Option<Vec<CosmosMsg>>,
+    pub fee: Option<FeeSource>,              // no platform fee if None
+    pub cancel_window: Option<CancelWindow>, // before the deadline if None
+    /// Keeps the campaign pending, contributions rejected, until every receiver calls
+    /// `AcceptReceiverRole`.
+    pub require_receiver_acceptance: Option<bool>,
+    pub guardian: Option<GuardianConfig>, // cannot be paused if None
+    /// Merkle root of the addresses allowed to pledge, see `merkle`. Open to anyone if None.
+    pub allowlist_root: Option<Binary>,
+    /// secp256k1 public key of the attester whose credentials contributors must present,
+    /// see `credential`. No credential needed if None.
+    pub attester: Option<Binary>,
+}
+
+/// Proof that the sender is on the allowlist, only needed once per root.
+#[cw_serde]
+pub struct AllowlistProof {
+    /// Sibling hashes from the sender's leaf up to the root.
+    pub proof: Vec<Binary>,
+    /// Most the sender may pledge, in the threshold's denom, if its leaf has a cap.
+    pub cap: Option<Uint128>,
 }
 
 #[cw_serde]
@@ -13,21 +62,161 @@ pub enum ExecuteMsg {
     /// User's contribution.
     ContributionMsg {
         coin: Coin,
+        allowlist: Option<AllowlistProof>,
+        credential: Option<SignedCredential>,
+        /// Address the pledge is recorded and refunded to, the sender if None.
+        /// Allowlist proof and credential are then the beneficiary's.
+        beneficiary: Option<String>,
+    },
+    /// Contribution split between several beneficiaries, the funds sent covering them all.
+    ContributionBatch {
+        contributions: Vec<BeneficiaryContribution>,
+    },
+    /// cw20 contribution, `msg` being a `ReceiveMsg`.
+    Receive(Cw20ReceiveMsg),
+    /// Pledge cw20 tokens the campaign is allowed to spend, pulled only on success.
+    PledgeAllowance {
+        amount: Uint128,
+        allowlist: Option<AllowlistProof>,
+        credential: Option<SignedCredential>,
+    },
+    /// Relayer submits a pledge signed off-chain by the contributor, see `intent`.
+    SubmitIntent(SignedIntent),
+    /// Withdraw the allowance pledge.
+    CancelPledge {},
+    /// Send a payout that failed at resolution again. Only the recipient may redirect it.
+    RetryPayout {
+        recipient: String,
+        redirect_to: Option<String>,
     },
     /// Trigger the resolution of the market. (TBD not sure about 'market' terminology).
     ResolveMsg {},
     RefundMsg {},
+    /// Take back part of the contribution, a full refund if it is the whole amount.
+    Withdraw {
+        amount: Uint128,
+        denom: Option<String>, // threshold denom if None
+    },
+    /// Move part or all of the pledge in `amount`'s denom to `to`, along with its refund
+    /// rights. Allowlist proof and credential are the recipient's.
+    TransferPledge {
+        to: String,
+        amount: Coin,
+        allowlist: Option<AllowlistProof>,
+        credential: Option<SignedCredential>,
+    },
+    /// Creator's proposal to move the deadline, subject to contributors' veto.
+    ProposeExtension {
+        deadline: Expiration,
+    },
+    /// Object to the pending extension and get refunded immediately.
+    ObjectExtension {},
+    /// Accept the pending extension, keeping the contribution in.
+    AcceptExtension {},
+    /// Owner calls the campaign off, making every contribution claimable.
+    Cancel {
+        reason: String,
+    },
+    /// Get the whole contribution back from a cancelled campaign.
+    ClaimRefund {},
+    /// Owner replaces the receivers, effective once they all accept the role.
+    ProposeReceivers {
+        receivers: Vec<ReceiverShare>,
+    },
+    /// Proposed receiver agrees to be paid by the campaign.
+    AcceptReceiverRole {},
+    /// Guardian suspends contributions and resolution, refunds stay available.
+    Pause {
+        reason: String,
+    },
+    /// Guardian lifts the pause before it ends on its own.
+    Unpause {},
+    /// Owner replaces the allowlist, None opening the campaign to anyone.
+    UpdateAllowlistRoot {
+        root: Option<Binary>,
+    },
+    /// Owner, or anyone relaying the attester's `signature` of the revocation (see
+    /// `credential::revocation_digest`), revokes the credential `nonce`, used or not.
+    RevokeCredential {
+        nonce: u64,
+        signature: Option<Binary>,
+    },
+    /// Owner hands the campaign over, effective once `new_owner` accepts.
+    ProposeOwner {
+        new_owner: String,
+    },
+    /// Proposed owner takes the campaign over.
+    AcceptOwnership {},
+    /// Owner gives up the campaign for good, disabling every privileged operation.
+    RenounceOwnership {},
+}
+
+/// Chain governance intervening in a stuck campaign.
+#[cw_serde]
+pub enum SudoMsg {
+    /// Cancel the campaign and refund every contribution right away.
+    ForceCancel { reason: String },
+    /// Resolve the campaign now, regardless of the deadline or a pause.
+    ForceResolve {},
+}
+
+#[cw_serde]
+pub struct BeneficiaryContribution {
+    pub beneficiary: String,
+    pub coin: Coin,
+}
+
+/// Message embedded in a cw20 `Send` to the campaign.
+#[cw_serde]
+pub enum ReceiveMsg {
+    Contribute {
+        allowlist: Option<AllowlistProof>,
+        credential: Option<SignedCredential>,
+    },
 }
 
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
+    /// Weighted value of a user's contribution, in the threshold's denom.
     #[returns(ContributionResponse)]
     GetUserContribution { addr: String },
+    /// Coins pledged by a user, one per denom.
+    #[returns(ContributionCoinsResponse)]
+    GetUserContributionCoins { addr: String },
     #[returns(ContributionTotalResponse)]
     GetTotalContribution {},
     #[returns(DeadlineResponse)]
     GetDeadline {},
+    /// Current total and threshold, evaluated at the current block time.
+    /// With an oracle, the total is valued at the latest price snapshot.
+    #[returns(StatusResponse)]
+    GetStatus {},
+    #[returns(ExtensionResponse)]
+    GetExtension {},
+    #[returns(ReceiversResponse)]
+    GetReceivers {},
+    /// Messages executed on success, and those that failed.
+    #[returns(ActionsResponse)]
+    GetActions {},
+    #[returns(PledgeResponse)]
+    GetPledge { addr: String },
+    /// Coins owed to `addr` after its payout failed.
+    #[returns(FailedPayoutResponse)]
+    GetFailedPayout { addr: String },
+    #[returns(OwnershipResponse)]
+    GetOwnership {},
+    #[returns(AllowlistResponse)]
+    GetAllowlist {},
+    /// Credential backing `addr`'s pledges.
+    #[returns(CredentialResponse)]
+    GetCredential { addr: String },
+    /// Last intent nonce used by the signer of `pubkey`.
+    #[returns(IntentNonceResponse)]
+    GetIntentNonce { pubkey: Binary },
+    /// Current pause, if any, and every pause before it.
+    #[returns(PauseResponse)]
+    GetPause {},
 }
 
 #[cw_serde]
@@ -35,6 +224,11 @@ pub struct ContributionResponse {
     pub coin: Coin,
 }
 
+#[cw_serde]
+pub struct ContributionCoinsResponse {
+    pub coins: Vec<Coin>,
+}
+
 #[cw_serde]
 pub struct ContributionTotalResponse {
     pub cointotal: Coin,
@@ -42,7 +236,96 @@ pub struct ContributionTotalResponse {
 
 #[cw_serde]
 pub struct DeadlineResponse {
-    pub timestamp: Timestamp,
+    pub deadline: Expiration,
+    pub opening: Option<Scheduled>,
+}
+
+#[cw_serde]
+pub struct StatusResponse {
+    pub total: Coin,
+    /// Allowance pledges, counting only if they can be pulled at resolution.
+    pub pledged: Coin,
+    pub threshold: Coin,
+    pub threshold_reached: bool,
+    /// Whether the campaign waits for its receivers to accept before opening.
+    pub pending: bool,
+    /// Set once the campaign is resolved or cancelled.
+    pub outcome: Option<Outcome>,
+    pub fee: Option<FeeConfig>,
+    /// Fee that would be taken from the current total on success.
+    pub fee_amount: Coin,
+}
+
+#[cw_serde]
+pub struct PledgeResponse {
+    pub amount: Uint128,
+    /// Part of the pledge that could not be pulled on success.
+    pub defaulted: Uint128,
+}
+
+#[cw_serde]
+pub struct ReceiversResponse {
+    /// Empty while the campaign is pending.
+    pub receivers: Vec<ReceiverShare>,
+    pub proposal: Option<ReceiverProposal>,
+}
+
+#[cw_serde]
+pub struct ActionsResponse {
+    pub actions: Vec<CosmosMsg>,
+    /// Actions that failed on success, their funds going to the receivers.
+    pub failed: Vec<FailedAction>,
+}
+
+#[cw_serde]
+pub struct FailedAction {
+    /// Index in `actions`.
+    pub index: u32,
+    pub error: String,
+}
+
+#[cw_serde]
+pub struct PauseResponse {
+    pub guardian: Option<GuardianConfig>,
+    pub paused: bool,
+    /// Oldest first.
+    pub history: Vec<Pause>,
+}
+
+#[cw_serde]
+pub struct AllowlistResponse {
+    pub root: Option<Binary>,
+}
+
+#[cw_serde]
+pub struct CredentialResponse {
+    pub credential: Option<Credential>,
+    pub revoked: bool,
+}
+
+#[cw_serde]
+pub struct IntentNonceResponse {
+    pub nonce: Option<u64>,
+}
+
+#[cw_serde]
+pub struct OwnershipResponse {
+    /// None once renounced.
+    pub owner: Option<Addr>,
+    pub pending_owner: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct FailedPayoutResponse {
+    pub coins: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct ExtensionResponse {
+    pub config: Option<ExtensionConfig>,
+    pub proposed: u32,
+    /// Proposal whose veto window is still open.
+    pub pending: Option<ExtensionProposal>,
 }
 
 #[cw_serde]
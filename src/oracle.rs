@@ -0,0 +1,49 @@
+//! Interface of the price oracle valuing contributions in the threshold's reference unit.
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Decimal, QuerierWrapper, Timestamp};
+
+use crate::state::OracleConfig;
+use crate::ContractError;
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum OracleQueryMsg {
+    /// Price of one unit of `denom` in units of `reference`.
+    #[returns(PriceResponse)]
+    Price { denom: String, reference: String },
+}
+
+#[cw_serde]
+pub struct PriceResponse {
+    pub price: Decimal,
+    /// Time the price was last published.
+    pub updated_at: Timestamp,
+}
+
+/// Fetches the price of `denom`, rejecting zero and stale prices.
+pub fn query_price(
+    querier: &QuerierWrapper,
+    oracle: &OracleConfig,
+    denom: &str,
+    reference: &str,
+    now: Timestamp,
+) -> Result<Decimal, ContractError> {
+    let resp: PriceResponse = querier.query_wasm_smart(
+        &oracle.contract,
+        &OracleQueryMsg::Price {
+            denom: denom.to_string(),
+            reference: reference.to_string(),
+        },
+    )?;
+    if resp.updated_at.plus_seconds(oracle.max_staleness) < now {
+        return Err(ContractError::StalePrice {
+            denom: denom.to_string(),
+        });
+    }
+    if resp.price.is_zero() {
+        return Err(ContractError::InvalidDenomWeight {
+            denom: denom.to_string(),
+        });
+    }
+    Ok(resp.price)
+}
@@ -1,12 +1,390 @@
-use cosmwasm_std::{Addr, Coin, Timestamp, Uint128};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Binary, BlockInfo, Coin, CosmosMsg, Decimal, Timestamp, Uint128};
 use cw_storage_plus::{Item, Map};
+use cw_utils::{Duration, Expiration, Scheduled};
 
-/// Registry of addresses and the amount they sent to the contract's bank account.
-pub const CONTRIBUTIONS: Map<&Addr, Uint128> = Map::new("contributions");
+use crate::credential::Credential;
+
+/// Registry of addresses and the amount they sent to the contract's bank account, per denom.
+pub const CONTRIBUTIONS: Map<(&Addr, &str), Uint128> = Map::new("contributions");
+/// Accepted denoms and their conversion weight towards the threshold's denom.
+/// With an oracle, the weights are the latest price snapshot.
+pub const DENOM_WEIGHTS: Map<&str, Decimal> = Map::new("denom-weights");
+/// cw20 token accepted through `Receive`, its address standing for the denom.
+pub const CW20_TOKEN: Item<Addr> = Item::new("cw20-token");
+/// cw20 amounts pledged through an allowance, pulled only on success.
+pub const ALLOWANCE_PLEDGES: Map<&Addr, Uint128> = Map::new("allowance-pledges");
+/// Allowance pledges that could not be pulled on success, with the amount left unpaid.
+pub const DEFAULTED_PLEDGES: Map<&Addr, Uint128> = Map::new("defaulted-pledges");
+/// Share of an allowance pledge pulled by each resolution submessage, by reply id.
+/// Removed by `reply` once the submessage ran.
+pub const PULLS: Map<u64, Pull> = Map::new("pulls");
+/// Payout sent by each resolution submessage, by reply id. Removed by `reply` once the
+/// submessage ran.
+pub const PAYOUTS: Map<u64, Payout> = Map::new("payouts");
+/// Number of reply ids handed out so far.
+pub const REPLY_COUNT: Item<u64> = Item::new("reply-count");
+/// Coins owed to recipients whose payout failed, until retried.
+pub const FAILED_PAYOUTS: Map<&str, Vec<Coin>> = Map::new("failed-payouts");
+/// Set once the campaign is resolved or cancelled, which can only happen once.
+pub const OUTCOME: Item<Outcome> = Item::new("outcome");
+/// Until when the owner may cancel the campaign.
+pub const CANCEL_WINDOW: Item<CancelWindow> = Item::new("cancel-window");
+/// Oracle pricing contributions, absent if the weights are fixed.
+pub const ORACLE: Item<OracleConfig> = Item::new("oracle");
 
 /// Coin threshold of rewards.
 pub const THRESHOLD_COIN: Item<Coin> = Item::new("threshold-coin");
-/// Timestamp of when reward should be distributed.
-pub const DEADLINE: Item<Timestamp> = Item::new("deadline");
-/// Receiver of reward.
-pub const RECEIVER: Item<String> = Item::new("receiver");
+/// How the threshold amount evolves until the deadline.
+pub const THRESHOLD_SCHEDULE: Item<ThresholdSchedule> = Item::new("threshold-schedule");
+/// Timestamp of when the contract was instantiated.
+pub const START: Item<Timestamp> = Item::new("start");
+/// Height or time from which contributions are closed and reward can be distributed.
+pub const DEADLINE: Item<Expiration> = Item::new("deadline");
+/// Deadline set at instantiation, where the threshold schedule ends whatever the extensions.
+pub const SCHEDULE_END: Item<Expiration> = Item::new("schedule-end");
+/// Height or time before which contributions are rejected, if any.
+pub const OPENING: Item<Scheduled> = Item::new("opening");
+/// Receivers of reward and their shares, absent while the first ones have not all accepted.
+pub const RECEIVERS: Item<Vec<ReceiverShare>> = Item::new("receivers");
+/// Receivers replacing the current ones once they all accept the role.
+pub const RECEIVER_PROPOSAL: Item<ReceiverProposal> = Item::new("receiver-proposal");
+/// Basis points making up the whole reward.
+pub const TOTAL_BPS: u16 = 10_000;
+/// Platform fee taken from successful payouts, if any.
+pub const FEE: Item<FeeConfig> = Item::new("fee");
+/// Highest platform fee, in basis points.
+pub const MAX_FEE_BPS: u16 = 1_000;
+/// Messages executed by the campaign on success, before paying the receiver.
+pub const ACTIONS: Item<Vec<CosmosMsg>> = Item::new("actions");
+/// Reply id of the first action, the others following in order, once they are sent.
+pub const ACTION_REPLIES: Item<u64> = Item::new("action-replies");
+/// Error of each action that failed on success, by index in `ACTIONS`.
+pub const FAILED_ACTIONS: Map<u32, String> = Map::new("failed-actions");
+/// Whether receivers that are contracts are called with `hook::ThresholdReachedHook` on success.
+pub const RECEIVER_HOOK: Item<bool> = Item::new("receiver-hook");
+/// Owner of the campaign, allowed to propose deadline extensions and to cancel.
+/// Initially the creator, absent once renounced.
+pub const OWNER: Item<Addr> = Item::new("owner");
+/// Address the owner proposed to hand the campaign over to, until it accepts.
+pub const PENDING_OWNER: Item<Addr> = Item::new("pending-owner");
+
+/// Address allowed to pause the campaign, how long and how often, if any.
+pub const GUARDIAN: Item<GuardianConfig> = Item::new("guardian");
+/// Number of pauses so far, also the id of the latest one.
+pub const PAUSE_COUNT: Item<u32> = Item::new("pause-count");
+/// Every pause by id, lifted or not.
+pub const PAUSES: Map<u32, Pause> = Map::new("pauses");
+/// Most pauses a guardian may be given.
+pub const MAX_PAUSES: u32 = 10;
+/// Longest a single pause may last, 30 days.
+pub const MAX_PAUSE_SECONDS: u64 = 30 * 24 * 60 * 60;
+/// Same as `MAX_PAUSE_SECONDS` in blocks, of about 6 seconds each.
+pub const MAX_PAUSE_BLOCKS: u64 = MAX_PAUSE_SECONDS / 6;
+
+/// Merkle root of the addresses allowed to pledge (see `merkle`), absent if anyone may.
+pub const ALLOWLIST_ROOT: Item<Binary> = Item::new("allowlist-root");
+/// Addresses whose allowlist proof was verified, with the root it was verified against.
+pub const ALLOWLISTED: Map<&Addr, AllowlistEntry> = Map::new("allowlisted");
+
+/// secp256k1 public key of the attester signing eligibility credentials, absent if anyone may
+/// pledge without one.
+pub const ATTESTER: Item<Binary> = Item::new("attester");
+/// Latest credential accepted for each contributor.
+pub const CREDENTIALS: Map<&Addr, Credential> = Map::new("credentials");
+/// Nonces of the credentials accepted so far, with the contributor who presented them.
+pub const USED_NONCES: Map<u64, Addr> = Map::new("used-nonces");
+/// Nonces of the revoked credentials, with when they were revoked.
+pub const REVOKED_NONCES: Map<u64, Timestamp> = Map::new("revoked-nonces");
+
+/// Last pledge intent nonce used by each signer, by public key.
+pub const INTENT_NONCES: Map<&[u8], u64> = Map::new("intent-nonces");
+/// Native funds relayers fronted for pledge intents, by contributor, denom and relayer. They
+/// are repaid first when the pledge is given back.
+pub const FRONTED: Map<(&Addr, &str, &Addr), Uint128> = Map::new("fronted");
+
+/// Rules for deadline extensions, absent if the deadline is fixed.
+pub const EXTENSION_CONFIG: Item<ExtensionConfig> = Item::new("extension-config");
+/// Number of extensions proposed so far, also the id of the latest proposal.
+pub const EXTENSION_COUNT: Item<u32> = Item::new("extension-count");
+/// Latest extension proposal, until it is applied or superseded.
+pub const PENDING_EXTENSION: Item<ExtensionProposal> = Item::new("pending-extension");
+/// Votes on extension proposals by (proposal id, contributor): true if accepted, false if objected.
+pub const EXTENSION_VOTES: Map<(u32, &Addr), bool> = Map::new("extension-votes");
+/// Addresses that pledged, or were handed a pledge, while proposal `id` was open. They are
+/// not part of its tally and cannot object to it.
+pub const LATE_PLEDGERS: Map<(u32, &Addr), bool> = Map::new("late-pledgers");
+
+/// Smallest pledge a contributor may hold, if any.
+pub const MIN_CONTRIBUTION: Item<Uint128> = Item::new("min-contribution");
+
+/// What happens to refunds once the threshold is reached.
+pub const REFUND_POLICY: Item<RefundPolicy> = Item::new("refund-policy");
+/// Refund taxes kept in the pot per denom, paid to the receiver whatever the outcome.
+pub const REFUND_TAXES: Map<&str, Uint128> = Map::new("refund-taxes");
+
+/// Automatic extension of the deadline on late activity, absent if disabled.
+pub const ANTI_SNIPE: Item<AntiSnipeConfig> = Item::new("anti-snipe");
+/// Total automatic extension so far, in the deadline's unit (seconds or blocks).
+pub const AUTO_EXTENDED: Item<u64> = Item::new("auto-extended");
+
+#[cw_serde]
+pub struct ExtensionConfig {
+    /// How many times the creator may propose an extension.
+    pub max_extensions: u32,
+    /// How long contributors have to object to a proposal.
+    pub veto_period: Duration,
+    /// Fraction of the pledged amount whose objection vetoes a proposal.
+    pub veto_threshold: Decimal,
+}
+
+#[cw_serde]
+pub struct ExtensionProposal {
+    /// Deadline replacing the current one if the proposal is not vetoed.
+    pub deadline: Expiration,
+    /// End of the window during which contributors can object.
+    pub veto_ends: Expiration,
+    /// Total contributions when the proposal was made.
+    pub pledged: Uint128,
+    /// Contributions withdrawn by objecting contributors.
+    pub objected: Uint128,
+}
+
+#[cw_serde]
+pub struct AllowlistEntry {
+    pub root: Binary,
+    pub cap: Option<Uint128>,
+}
+
+#[cw_serde]
+pub struct GuardianConfig {
+    pub address: String,
+    /// Pauses are lifted automatically after this long.
+    pub max_pause: Duration,
+    /// Number of pauses the guardian may call over the campaign's life, so that it cannot hold
+    /// resolution off forever.
+    pub max_pauses: u32,
+}
+
+#[cw_serde]
+pub struct Pause {
+    pub reason: String,
+    pub paused_at: Timestamp,
+    /// Automatic end of the pause.
+    pub until: Expiration,
+    /// When the guardian lifted the pause before it ended, if it did.
+    pub lifted_at: Option<Timestamp>,
+}
+
+impl Pause {
+    pub fn is_active(&self, block: &BlockInfo) -> bool {
+        self.lifted_at.is_none() && !self.until.is_expired(block)
+    }
+}
+
+#[cw_serde]
+pub struct DenomWeight {
+    pub denom: String,
+    /// Value of one unit of `denom` in units of the threshold's denom.
+    pub weight: Decimal,
+}
+
+#[cw_serde]
+pub struct ReceiverShare {
+    pub address: String,
+    pub share_bps: u16,
+}
+
+#[cw_serde]
+pub struct ReceiverProposal {
+    pub receivers: Vec<ReceiverShare>,
+    /// Proposed receivers who accepted the role so far.
+    pub accepted: Vec<String>,
+}
+
+#[cw_serde]
+pub struct FeeConfig {
+    pub treasury: String,
+    pub fee_bps: u16,
+}
+
+#[cw_serde]
+pub enum FeeSource {
+    Custom(FeeConfig),
+    /// Inherit the fee of the factory instantiating the campaign.
+    Factory {},
+}
+
+#[cw_serde]
+pub enum Outcome {
+    Resolved {},
+    Cancelled { reason: String },
+}
+
+#[cw_serde]
+pub enum CancelWindow {
+    BeforeDeadline {},
+    /// Before the deadline, as long as the threshold is not reached.
+    BeforeSuccess {},
+}
+
+#[cw_serde]
+pub struct Payout {
+    pub recipient: String,
+    pub coins: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct Pull {
+    /// Contributor whose allowance is drawn on.
+    pub owner: Addr,
+    pub recipient: String,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct OracleConfig {
+    /// Contract answering `oracle::OracleQueryMsg`.
+    pub contract: String,
+    /// Denoms accepted, priced in the threshold's denom.
+    pub denoms: Vec<String>,
+    /// Maximum age of a price, in seconds.
+    pub max_staleness: u64,
+}
+
+#[cw_serde]
+pub enum RefundPolicy {
+    /// Refunds stay available until the deadline.
+    Free {},
+    /// Refunds are disabled once the total reaches the threshold.
+    LockOnThreshold {},
+    /// Once the total reaches the threshold, refunds leave `tax` of the amount in the pot.
+    TaxOnThreshold { tax: Decimal },
+}
+
+#[cw_serde]
+pub struct AntiSnipeConfig {
+    /// Contributions and refunds this close to the deadline extend it.
+    pub window: Duration,
+    /// How far each late contribution or refund pushes the deadline.
+    pub extension: Duration,
+    /// Cap on the total automatic extension.
+    pub max_extension: Duration,
+}
+
+impl AntiSnipeConfig {
+    /// Next extension after `used` was already spent, in the deadline's unit.
+    pub fn next_extension(&self, used: u64) -> u64 {
+        duration_units(&self.extension)
+            .min(duration_units(&self.max_extension).saturating_sub(used))
+    }
+}
+
+/// Whether `duration` can be added to `expiration`.
+pub fn same_unit(duration: &Duration, expiration: &Expiration) -> bool {
+    matches!(
+        (duration, expiration),
+        (Duration::Height(_), Expiration::AtHeight(_)) | (Duration::Time(_), Expiration::AtTime(_))
+    )
+}
+
+fn duration_units(duration: &Duration) -> u64 {
+    match duration {
+        Duration::Height(blocks) => *blocks,
+        Duration::Time(seconds) => *seconds,
+    }
+}
+
+impl ExtensionProposal {
+    /// Whether objections stayed below `veto_threshold` of the pledged amount.
+    /// A proposal nobody objected to is always approved.
+    pub fn is_approved(&self, veto_threshold: Decimal) -> bool {
+        self.objected.is_zero() || self.objected < self.pledged * veto_threshold
+    }
+}
+
+/// Threshold as a function of time, starting from the `THRESHOLD_COIN` amount.
+#[cw_serde]
+pub enum ThresholdSchedule {
+    /// The threshold stays at its initial amount.
+    Constant {},
+    /// The threshold decreases linearly from its initial amount at instantiation
+    /// down to `floor` at the initial deadline, extensions leaving it unchanged.
+    /// Requires a time-based deadline.
+    Linear { floor: Uint128 },
+    /// The threshold drops to each step's amount once the step's time is reached.
+    Stepwise { steps: Vec<ThresholdStep> },
+}
+
+#[cw_serde]
+pub struct ThresholdStep {
+    pub at: Timestamp,
+    pub amount: Uint128,
+}
+
+impl ThresholdSchedule {
+    /// Checks that the schedule never rises above the initial amount and only decreases.
+    pub fn validate(&self, initial: Uint128, deadline: &Expiration) -> Result<(), String> {
+        match self {
+            ThresholdSchedule::Constant {} => Ok(()),
+            ThresholdSchedule::Linear { floor } => {
+                if !matches!(deadline, Expiration::AtTime(_)) {
+                    return Err("linear schedule requires a time-based deadline".to_string());
+                }
+                if *floor > initial {
+                    return Err("floor is above the initial threshold".to_string());
+                }
+                Ok(())
+            }
+            ThresholdSchedule::Stepwise { steps } => {
+                let mut previous: Option<&ThresholdStep> = None;
+                for step in steps {
+                    if step.amount > initial {
+                        return Err("step is above the initial threshold".to_string());
+                    }
+                    if let Some(previous) = previous {
+                        if step.at <= previous.at {
+                            return Err("steps must be in increasing time order".to_string());
+                        }
+                        if step.amount > previous.amount {
+                            return Err("steps must not increase the threshold".to_string());
+                        }
+                    }
+                    previous = Some(step);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Threshold amount at time `now`, for a schedule running from `start` to `deadline`.
+    pub fn threshold_at(
+        &self,
+        initial: Uint128,
+        start: Timestamp,
+        deadline: &Expiration,
+        now: Timestamp,
+    ) -> Uint128 {
+        match (self, deadline) {
+            (ThresholdSchedule::Constant {}, _) => initial,
+            (ThresholdSchedule::Linear { floor }, Expiration::AtTime(deadline)) => {
+                let deadline = *deadline;
+                if now <= start {
+                    initial
+                } else if now >= deadline {
+                    *floor
+                } else {
+                    let elapsed = now.seconds() - start.seconds();
+                    let duration = deadline.seconds() - start.seconds();
+                    initial - (initial - floor).multiply_ratio(elapsed, duration)
+                }
+            }
+            (ThresholdSchedule::Linear { .. }, _) => initial,
+            (ThresholdSchedule::Stepwise { steps }, _) => steps
+                .iter()
+                .take_while(|step| step.at <= now)
+                .last()
+                .map_or(initial, |step| step.amount),
+        }
+    }
+}